@@ -0,0 +1,151 @@
+use crate::{check_rep_on_unwind, CheckRep, Violations};
+use std::ops::{Deref, DerefMut};
+
+/// A value whose representation is checked whenever it is mutated
+///
+/// The value can be read through `Deref` or `get`. It can only be mutated through the guard
+/// returned by `get_mut`, which checks representation when dropped, or through `modify`. This
+/// covers mutations that `#[check_rep]` can't reach, such as assignments to public fields.
+///
+/// With the `serde` feature enabled, `Checked<T>` can be deserialized wherever `T` can. The value
+/// is checked once deserialized and violations become a deserialization error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checked<T> {
+    value: T,
+}
+
+impl<T: CheckRep> Checked<T> {
+    /// Wraps the value if its representation is correct, returns the violations found otherwise
+    pub fn new(value: T) -> Result<Self, Violations> {
+        value.violations()?;
+        Ok(Self { value })
+    }
+
+    /// Unwraps the value
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Returns a reference to the value
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Returns a guard that allows mutating the value and calls `check_rep` when dropped
+    pub fn get_mut(&mut self) -> CheckedMut<'_, T> {
+        CheckedMut {
+            value: &mut self.value,
+        }
+    }
+}
+
+impl<T: CheckRep + Clone> Checked<T> {
+    /// Mutates the value with the given function and returns what it returns if representation
+    /// is still correct, undoes the mutation and returns the violations found otherwise
+    ///
+    /// Unlike mutating through `get_mut`, violations are returned rather than handled, and the
+    /// value is never left incorrect. It is cloned before it is mutated, as with `transact`.
+    pub fn modify<R, F: FnOnce(&mut T) -> R>(&mut self, f: F) -> Result<R, Violations> {
+        crate::transact(&mut self.value, f)
+    }
+}
+
+impl<T> Deref for Checked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+/// A guard that allows mutating a `Checked` value and calls `check_rep` when dropped
+///
+/// If the guard is dropped because of a panic, violations are reported without panicking again.
+pub struct CheckedMut<'a, T: CheckRep> {
+    value: &'a mut T,
+}
+
+impl<T: CheckRep> Deref for CheckedMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T: CheckRep> DerefMut for CheckedMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<T: CheckRep> Drop for CheckedMut<'_, T> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            check_rep_on_unwind(self.value);
+        } else {
+            self.value.check_rep();
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: CheckRep + serde::Deserialize<'de>> serde::Deserialize<'de> for Checked<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = T::deserialize(deserializer)?;
+        Checked::new(value).map_err(|violations| {
+            serde::de::Error::custom(format!("representation invariant violated: {}", violations))
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for Checked<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::Limits;
+
+    #[test]
+    fn new_checks_value() {
+        let violations = Checked::new(Limits { lo: 2, hi: 1 }).unwrap_err();
+        assert_eq!(violations.to_strings(), vec!["lo must be <= hi, not 2 > 1"]);
+        assert_eq!(Checked::new(Limits { lo: 1, hi: 2 }).unwrap().hi, 2);
+    }
+
+    #[test]
+    fn modify_returns_result() {
+        let mut limits = Checked::new(Limits { lo: 0, hi: 10 }).unwrap();
+        assert_eq!(limits.modify(|l| std::mem::replace(&mut l.lo, 5)).unwrap(), 0);
+        assert_eq!(limits.lo, 5);
+    }
+
+    #[test]
+    fn modify_rolls_back() {
+        let mut limits = Checked::new(Limits { lo: 0, hi: 10 }).unwrap();
+        let violations = limits.modify(|l| l.hi = -1).unwrap_err();
+        assert_eq!(violations.to_strings(), vec!["lo must be <= hi, not 0 > -1"]);
+        assert_eq!(limits.into_inner(), Limits { lo: 0, hi: 10 });
+    }
+
+    #[test]
+    fn get_mut_allows_correct_mutation() {
+        let mut limits = Checked::new(Limits { lo: 0, hi: 10 }).unwrap();
+        limits.get_mut().lo = 10;
+        assert_eq!(limits.get(), &Limits { lo: 10, hi: 10 });
+    }
+
+    // the check is compiled out with `off`, and with `debug-only` in release builds
+    #[test]
+    #[cfg(not(any(feature = "off", all(feature = "debug-only", not(debug_assertions)))))]
+    #[should_panic]
+    fn get_mut_checks_on_drop() {
+        let mut limits = Checked::new(Limits { lo: 0, hi: 10 }).unwrap();
+        limits.get_mut().lo = 11;
+    }
+}
@@ -0,0 +1,20 @@
+//! Values shared by the unit tests
+
+use crate::CheckRep;
+
+/// A pair of bounds whose representation is correct when `lo <= hi`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Limits {
+    pub(crate) lo: i32,
+    pub(crate) hi: i32,
+}
+
+impl CheckRep for Limits {
+    fn correctness(&self) -> Result<(), Vec<String>> {
+        if self.lo <= self.hi {
+            Ok(())
+        } else {
+            Err(vec![format!("lo must be <= hi, not {} > {}", self.lo, self.hi)])
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{CheckRep, Violations};
+use log::error;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// What to do when representation invariants are found to be violated
+pub enum ViolationHandler {
+    /// Panic with a message for each violation
+    Panic,
+    /// Log each violation as an error and carry on
+    Log,
+    /// Log each violation as an error and then panic
+    LogThenPanic,
+    /// Print each violation to standard error and abort the process
+    Abort,
+    /// Count the violations and carry on, see `violation_count`
+    Count,
+    /// Do nothing
+    Ignore,
+    /// Call the given function with the violations
+    Custom(Box<dyn Fn(&Violations) + Send + Sync>),
+}
+
+impl ViolationHandler {
+    /// Creates a handler that calls the given function with the violations
+    pub fn custom<F: Fn(&Violations) + Send + Sync + 'static>(f: F) -> Self {
+        ViolationHandler::Custom(Box::new(f))
+    }
+}
+
+static HANDLER: RwLock<Option<Arc<ViolationHandler>>> = RwLock::new(None);
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets how violations are handled from now on, by all threads
+///
+/// Until this is called, violations cause a panic.
+pub fn set_violation_handler(handler: ViolationHandler) {
+    *HANDLER.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Arc::new(handler));
+}
+
+/// Returns the number of violations counted by `ViolationHandler::Count`
+pub fn violation_count() -> usize {
+    COUNT.load(Ordering::SeqCst)
+}
+
+/// Resets the number of violations counted by `ViolationHandler::Count` to 0
+pub fn reset_violation_count() {
+    COUNT.store(0, Ordering::SeqCst);
+}
+
+/// Handles violations with the handler that is currently set
+///
+/// This is what `check_rep` calls when representation is incorrect.
+pub fn handle_violations(violations: &Violations) {
+    // the lock is not held while handling so that handlers may panic or set a new handler
+    let handler = HANDLER.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+
+    match handler.as_deref() {
+        None | Some(ViolationHandler::Panic) => {
+            panic!("representation invariant violated: {:?}", violations.to_strings());
+        }
+        Some(ViolationHandler::Log) => {
+            log_violations(violations);
+        }
+        Some(ViolationHandler::LogThenPanic) => {
+            log_violations(violations);
+            panic!("representation invariant violated: {:?}", violations.to_strings());
+        }
+        Some(ViolationHandler::Abort) => {
+            for violation in violations {
+                eprintln!("representation invariant violated: {}", violation);
+            }
+            std::process::abort();
+        }
+        Some(ViolationHandler::Count) => {
+            COUNT.fetch_add(violations.len(), Ordering::SeqCst);
+        }
+        Some(ViolationHandler::Ignore) => {}
+        Some(ViolationHandler::Custom(f)) => {
+            f(violations);
+        }
+    }
+}
+
+/// Checks representation of a value whose mutation was cut short by a panic
+///
+/// Violations are marked as left behind by a panic and handled with the current handler. A panic
+/// raised by the handler is caught, after being reported by the panic hook, so that it doesn't
+/// replace the panic that is already in progress.
+pub fn check_rep_on_unwind<T: CheckRep + ?Sized>(value: &T) {
+    if let Err(mut violations) = value.violations() {
+        for violation in violations.iter_mut() {
+            violation.message.push_str(" (left behind by a method that panicked)");
+        }
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| handle_violations(&violations)));
+    }
+}
+
+fn log_violations(violations: &Violations) {
+    for violation in violations {
+        error!("representation invariant violated: {}", violation);
+    }
+}
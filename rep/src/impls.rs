@@ -0,0 +1,303 @@
+use crate::{CheckRep, Violations};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Checks each element in turn, making violations relative to `self[index]`
+fn elements_violations<'a, T: CheckRep + 'a, I: IntoIterator<Item = &'a T>>(elements: I) -> Result<(), Violations> {
+    let mut violations = Violations::new();
+    for (i, element) in elements.into_iter().enumerate() {
+        if let Err(mut errors) = element.violations() {
+            errors.within(&format!("self[{}]", i));
+            violations.append(&mut errors);
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+/// Checks each value of a map in turn, making violations relative to `self[key]`
+fn entries_violations<'a, K: Debug + 'a, V: CheckRep + 'a, I: IntoIterator<Item = (&'a K, &'a V)>>(
+    entries: I,
+) -> Result<(), Violations> {
+    let mut violations = Violations::new();
+    for (key, value) in entries {
+        if let Err(mut errors) = value.violations() {
+            errors.within(&format!("self[{:?}]", key));
+            violations.append(&mut errors);
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+// containers that hold a single value are checked as that value
+macro_rules! impl_check_rep_deref {
+    ($($container:ident),*) => {
+        $(
+            impl<T: CheckRep + ?Sized> CheckRep for $container<T> {
+                fn is_correct(&self) -> bool {
+                    (**self).is_correct()
+                }
+
+                fn correctness(&self) -> Result<(), Vec<String>> {
+                    (**self).correctness()
+                }
+
+                fn violations(&self) -> Result<(), Violations> {
+                    (**self).violations()
+                }
+            }
+        )*
+    };
+}
+
+impl_check_rep_deref!(Box, Rc, Arc);
+
+/// `None` is always correct
+impl<T: CheckRep> CheckRep for Option<T> {
+    fn is_correct(&self) -> bool {
+        match self {
+            Some(value) => value.is_correct(),
+            None => true,
+        }
+    }
+
+    fn correctness(&self) -> Result<(), Vec<String>> {
+        self.violations().map_err(|violations| violations.to_strings())
+    }
+
+    fn violations(&self) -> Result<(), Violations> {
+        match self {
+            Some(value) => value.violations(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A value that is mutably borrowed can't be checked and is taken to be correct
+impl<T: CheckRep + ?Sized> CheckRep for RefCell<T> {
+    fn is_correct(&self) -> bool {
+        self.try_borrow().map_or(true, |value| value.is_correct())
+    }
+
+    fn correctness(&self) -> Result<(), Vec<String>> {
+        self.violations().map_err(|violations| violations.to_strings())
+    }
+
+    fn violations(&self) -> Result<(), Violations> {
+        match self.try_borrow() {
+            Ok(value) => value.violations(),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+impl<T: CheckRep> CheckRep for [T] {
+    fn is_correct(&self) -> bool {
+        self.iter().all(|element| element.is_correct())
+    }
+
+    fn correctness(&self) -> Result<(), Vec<String>> {
+        self.violations().map_err(|violations| violations.to_strings())
+    }
+
+    fn violations(&self) -> Result<(), Violations> {
+        elements_violations(self)
+    }
+}
+
+impl<T: CheckRep, const N: usize> CheckRep for [T; N] {
+    fn is_correct(&self) -> bool {
+        self[..].is_correct()
+    }
+
+    fn correctness(&self) -> Result<(), Vec<String>> {
+        self[..].correctness()
+    }
+
+    fn violations(&self) -> Result<(), Violations> {
+        self[..].violations()
+    }
+}
+
+impl<T: CheckRep> CheckRep for Vec<T> {
+    fn is_correct(&self) -> bool {
+        self[..].is_correct()
+    }
+
+    fn correctness(&self) -> Result<(), Vec<String>> {
+        self[..].correctness()
+    }
+
+    fn violations(&self) -> Result<(), Violations> {
+        self[..].violations()
+    }
+}
+
+/// Only values are checked, violations are reported for their keys
+impl<K: Debug, V: CheckRep, S> CheckRep for HashMap<K, V, S> {
+    fn is_correct(&self) -> bool {
+        self.values().all(|value| value.is_correct())
+    }
+
+    fn correctness(&self) -> Result<(), Vec<String>> {
+        self.violations().map_err(|violations| violations.to_strings())
+    }
+
+    fn violations(&self) -> Result<(), Violations> {
+        entries_violations(self)
+    }
+}
+
+/// Only values are checked, violations are reported for their keys
+impl<K: Debug, V: CheckRep> CheckRep for BTreeMap<K, V> {
+    fn is_correct(&self) -> bool {
+        self.values().all(|value| value.is_correct())
+    }
+
+    fn correctness(&self) -> Result<(), Vec<String>> {
+        self.violations().map_err(|violations| violations.to_strings())
+    }
+
+    fn violations(&self) -> Result<(), Violations> {
+        entries_violations(self)
+    }
+}
+
+// tuples are checked element by element, making violations relative to `self.<index>`
+macro_rules! impl_check_rep_tuple {
+    ($(($($element:ident $index:tt),+))*) => {
+        $(
+            impl<$($element: CheckRep),+> CheckRep for ($($element,)+) {
+                fn is_correct(&self) -> bool {
+                    $(self.$index.is_correct())&&+
+                }
+
+                fn correctness(&self) -> Result<(), Vec<String>> {
+                    self.violations().map_err(|violations| violations.to_strings())
+                }
+
+                fn violations(&self) -> Result<(), Violations> {
+                    let mut violations = Violations::new();
+                    $(
+                        if let Err(mut errors) = self.$index.violations() {
+                            errors.within(concat!("self.", stringify!($index)));
+                            violations.append(&mut errors);
+                        }
+                    )+
+
+                    if violations.is_empty() {
+                        Ok(())
+                    } else {
+                        Err(violations)
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_check_rep_tuple! {
+    (A 0)
+    (A 0, B 1)
+    (A 0, B 1, C 2)
+    (A 0, B 1, C 2, D 3)
+    (A 0, B 1, C 2, D 3, E 4)
+    (A 0, B 1, C 2, D 3, E 4, F 5)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Violation;
+
+    #[derive(Debug)]
+    struct Positive(i32);
+
+    impl CheckRep for Positive {
+        fn violations(&self) -> Result<(), Violations> {
+            if self.0 > 0 {
+                Ok(())
+            } else {
+                Err(Violation {
+                    type_name: String::from("Positive"),
+                    variant: None,
+                    path: String::from("self.0"),
+                    rule: String::from("assert_gt"),
+                    expected: Some(String::from("> 0")),
+                    actual: Some(self.0.to_string()),
+                    message: format!("self.0 must be > 0, not {}", self.0),
+                }
+                .into())
+            }
+        }
+    }
+
+    fn messages<T: CheckRep + ?Sized>(value: &T) -> Vec<String> {
+        value.violations().map(|()| vec![]).unwrap_or_else(|violations| violations.to_strings())
+    }
+
+    #[test]
+    fn containers_of_one_value() {
+        assert_eq!(messages(&Box::new(Positive(0))), vec!["self.0 must be > 0, not 0"]);
+        assert_eq!(messages(&Rc::new(Positive(-1))), vec!["self.0 must be > 0, not -1"]);
+        assert_eq!(messages(&Arc::new(Positive(-2))), vec!["self.0 must be > 0, not -2"]);
+        assert_eq!(messages(&Some(Positive(0))), vec!["self.0 must be > 0, not 0"]);
+        assert!(None::<Positive>.is_correct());
+    }
+
+    #[test]
+    fn mutably_borrowed_ref_cell_is_correct() {
+        let cell = RefCell::new(Positive(0));
+        assert!(!cell.is_correct());
+        let _borrow = cell.borrow_mut();
+        assert!(cell.is_correct());
+        assert!(cell.violations().is_ok());
+    }
+
+    #[test]
+    fn sequences() {
+        let expected = vec!["self[1].0 must be > 0, not 0", "self[2].0 must be > 0, not -1"];
+        let array = [Positive(1), Positive(0), Positive(-1)];
+        assert_eq!(messages(&array), expected);
+        assert_eq!(messages(&array[..]), expected);
+        assert_eq!(messages(&vec![Positive(1), Positive(0), Positive(-1)]), expected);
+        assert!(!array.is_correct());
+        assert!(Vec::<Positive>::new().is_correct());
+    }
+
+    #[test]
+    fn maps() {
+        let mut btree_map = BTreeMap::new();
+        btree_map.insert("alice", Positive(3));
+        btree_map.insert("bob", Positive(0));
+        assert_eq!(messages(&btree_map), vec!["self[\"bob\"].0 must be > 0, not 0"]);
+
+        let mut hash_map = HashMap::new();
+        hash_map.insert(7, Positive(-7));
+        assert_eq!(messages(&hash_map), vec!["self[7].0 must be > 0, not -7"]);
+        assert!(!hash_map.is_correct());
+    }
+
+    #[test]
+    fn tuples() {
+        assert_eq!(
+            messages(&(Positive(0), Positive(1), vec![Positive(-1)])),
+            vec!["self.0.0 must be > 0, not 0", "self.2[0].0 must be > 0, not -1"]
+        );
+        assert!((Positive(1),).is_correct());
+    }
+}
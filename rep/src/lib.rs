@@ -5,56 +5,135 @@ pub use rep_derive::check_rep;
 pub use log::Level::Error;
 pub use log::{error, log_enabled};
 
+mod checked;
+#[cfg(test)]
+mod fixtures;
+mod handler;
+mod impls;
+mod sampling;
+mod transact;
+mod violation;
+
+pub use checked::{Checked, CheckedMut};
+pub use handler::{
+    check_rep_on_unwind, handle_violations, reset_violation_count, set_violation_handler, violation_count,
+    ViolationHandler,
+};
+pub use sampling::{reset_stats, set_sampling, set_type_sampling, should_check, stats, CheckStats, Sampling};
+pub use transact::{rollback_if_incorrect, transact};
+pub use violation::{Violation, Violations};
+
+/// Returns true if checks should run in a build with or without debug assertions
+///
+/// This is decided by the features of `rep`. With `off`, checks never run. With `debug-only`,
+/// checks only run in builds with debug assertions. Otherwise, as with the default `always`
+/// feature, checks always run. Code generated by the macros passes `cfg!(debug_assertions)` so
+/// that disabled checks are optimized away.
+pub const fn checks_enabled(debug_assertions: bool) -> bool {
+    if cfg!(feature = "off") {
+        false
+    } else if cfg!(feature = "debug-only") {
+        debug_assertions
+    } else {
+        true
+    }
+}
+
 /// A trait for representation checking
+///
+/// It is implemented for `Option`, `Box`, `Rc`, `Arc`, `RefCell`, slices, arrays, `Vec`,
+/// `HashMap`, `BTreeMap` and tuples of types that implement it, by checking the values they
+/// contain. Violations found in an element are reported at paths such as `self[3]`,
+/// `self["alice"]` or `self.0`. A `RefCell` that is mutably borrowed is taken to be correct.
+///
+/// Implementations override at least one of `is_correct`, `correctness` and `violations`, the
+/// others are derived from it.
 pub trait CheckRep {
-	/// Returns true if representation is correct, false otherwise
+    /// Returns true if representation is correct, false otherwise
     fn is_correct(&self) -> bool {
-    	self.correctness().is_ok()
+        self.violations().is_ok()
     }
 
     /// Returns Ok if representation is correct, vector of errors otherwise
     fn correctness(&self) -> Result<(), Vec<String>> {
-    	if self.is_correct() {
-    		Ok(())
-    	} else {
-    		Err(vec![])
-    	}
+        if self.is_correct() {
+            Ok(())
+        } else {
+            Err(vec![])
+        }
+    }
+
+    /// Returns Ok if representation is correct, the invariants that don't hold otherwise
+    ///
+    /// By default the errors returned by `correctness` are turned into custom violations.
+    fn violations(&self) -> Result<(), Violations> {
+        self.correctness().map_err(|errors| Violations::from_messages(std::any::type_name::<Self>(), errors))
     }
 
     /// Asserts that self is correct
-	fn check_rep(&self) {
-		match self.correctness() {
-			Ok(_) => {}
-			Err(errors) => {
-				if log_enabled!(Error) {
-					for error in errors {
-						error!("representation invariant violated: {:?}", error);
-					}
-				} else {
-					if errors.len() > 0 {
-						panic!("representation invariant violated: {:?}", errors);	
-					} else {
-						panic!("representation invariant violated");	
-					}
-				}
-			}
-		}
-	}
+    ///
+    /// Violations are handled by the handler set with `set_violation_handler`, which panics by
+    /// default. Nothing is checked if checks are disabled, see `checks_enabled`.
+    fn check_rep(&self) {
+        if !checks_enabled(cfg!(debug_assertions)) {
+            return;
+        }
+        if let Err(violations) = self.violations() {
+            handle_violations(&violations);
+        }
+    }
 }
 
 /// A trait for adding extra rep-checking functionality to a data structure with `CheckRep` implemented
+///
+/// Implementations override at least one of `c_is_correct`, `c_correctness` and `c_violations`,
+/// the others are derived from it.
 pub trait CustomCheckRep {
-	/// Returns true if representation is correct, false otherwise
+    /// Returns true if representation is correct, false otherwise
     fn c_is_correct(&self) -> bool {
-    	self.c_correctness().is_ok()
+        self.c_violations().is_ok()
     }
 
     /// Returns Ok if representation is correct, vector of errors otherwise
     fn c_correctness(&self) -> Result<(), Vec<String>> {
-    	if self.c_is_correct() {
-    		Ok(())
-    	} else {
-    		Err(vec![])
-    	}
+        if self.c_is_correct() {
+            Ok(())
+        } else {
+            Err(vec![])
+        }
+    }
+
+    /// Returns Ok if representation is correct, the invariants that don't hold otherwise
+    ///
+    /// By default the errors returned by `c_correctness` are turned into custom violations.
+    fn c_violations(&self) -> Result<(), Violations> {
+        self.c_correctness().map_err(|errors| Violations::from_messages(std::any::type_name::<Self>(), errors))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // overrides only `violations`, which the other methods must go through
+    struct Odd(u32);
+
+    impl CheckRep for Odd {
+        fn violations(&self) -> Result<(), Violations> {
+            if self.0 % 2 == 1 {
+                Ok(())
+            } else {
+                Err(Violation::custom("Odd", format!("{} must be odd", self.0)).into())
+            }
+        }
+    }
+
+    #[test]
+    fn defaults_go_through_violations() {
+        assert!(Odd(3).is_correct());
+        assert!(!Odd(4).is_correct());
+        assert!(Odd(3).correctness().is_ok());
+        assert!(Odd(4).correctness().is_err());
+        assert!(!vec![Odd(1), Odd(2)].is_correct());
+    }
+}
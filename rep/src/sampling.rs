@@ -0,0 +1,278 @@
+use std::any::type_name;
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// How often generated checks of a type's representation run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sampling {
+    /// Run every check
+    Always,
+    /// Run one in every n checks, starting with the first, where an n of 0 is taken to be 1
+    EveryNth(u64),
+    /// Run each check with the given probability, from 0 to 1
+    Probability(f64),
+}
+
+/// How many generated checks of a type's representation ran or were skipped
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CheckStats {
+    /// Number of checks that ran
+    pub executed: u64,
+    /// Number of checks that were skipped because of sampling
+    pub skipped: u64,
+}
+
+// a sampling is encoded in a single word so that it can be read without a lock, with its kind in
+// the top 2 bits and its parameter in the others
+const KIND_SHIFT: u32 = 62;
+const PARAMETER: u64 = (1 << KIND_SHIFT) - 1;
+const UNSET: u64 = 0;
+const ALWAYS: u64 = 1 << KIND_SHIFT;
+const EVERY_NTH: u64 = 2;
+const PROBABILITY: u64 = 3;
+// probabilities are encoded as a threshold for random numbers of this many bits
+const RANDOM_BITS: u32 = 53;
+
+fn encode(sampling: Sampling) -> u64 {
+    match sampling {
+        Sampling::Always => ALWAYS,
+        Sampling::EveryNth(n) => EVERY_NTH << KIND_SHIFT | n.clamp(1, PARAMETER),
+        Sampling::Probability(p) => {
+            PROBABILITY << KIND_SHIFT | (p.clamp(0.0, 1.0) * (1u64 << RANDOM_BITS) as f64) as u64
+        }
+    }
+}
+
+/// The sampling and counts of a type, shared by all threads
+#[derive(Default)]
+struct TypeSampler {
+    // encoded sampling of the type, UNSET if it follows the global one
+    sampling: AtomicU64,
+    // number of checks sampled every nth time, kept apart from the stats so that resetting them
+    // doesn't change which checks run
+    calls: AtomicU64,
+    executed: AtomicU64,
+    skipped: AtomicU64,
+}
+
+// set once sampling is configured, so that checks don't do anything else until then
+static CONFIGURED: AtomicBool = AtomicBool::new(false);
+static GLOBAL: AtomicU64 = AtomicU64::new(ALWAYS);
+// every type checked or configured since, by name, which is only locked the first time a thread
+// checks a type
+static TYPES: Mutex<Option<HashMap<&'static str, &'static TypeSampler>>> = Mutex::new(None);
+
+thread_local! {
+    static CACHE: RefCell<HashMap<&'static str, &'static TypeSampler>> = RefCell::new(HashMap::new());
+    // state of the xorshift generator used for probabilities
+    static RANDOM: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+fn with_types<R, F: FnOnce(&mut HashMap<&'static str, &'static TypeSampler>) -> R>(f: F) -> R {
+    let mut types = TYPES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(types.get_or_insert_with(HashMap::new))
+}
+
+/// Returns the sampler of the type with the given name, creating it if needed
+fn registered(name: &'static str) -> &'static TypeSampler {
+    // samplers live as long as the program, there is one per type
+    with_types(|types| *types.entry(name).or_insert_with(|| Box::leak(Box::default())))
+}
+
+/// Returns the sampler of the type with the given name, without locking after the first call
+/// from a thread
+fn type_sampler(name: &'static str) -> &'static TypeSampler {
+    CACHE
+        .try_with(|cache| *cache.borrow_mut().entry(name).or_insert_with(|| registered(name)))
+        .unwrap_or_else(|_| registered(name))
+}
+
+/// Returns a number of `RANDOM_BITS` random bits
+fn next_random() -> u64 {
+    let next = |mut random: u64| {
+        random ^= random << 13;
+        random ^= random >> 7;
+        random ^= random << 17;
+        random
+    };
+
+    let random = RANDOM
+        .try_with(|random| {
+            random.set(next(random.get()));
+            random.get()
+        })
+        .unwrap_or_else(|_| next(RandomState::new().build_hasher().finish() | 1));
+    random >> (64 - RANDOM_BITS)
+}
+
+/// Sets how often generated checks run for types without their own sampling, see
+/// `set_type_sampling`
+///
+/// Until this is called, every check runs.
+pub fn set_sampling(sampling: Sampling) {
+    GLOBAL.store(encode(sampling), Ordering::Relaxed);
+    with_types(|types| {
+        for sampler in types.values() {
+            sampler.calls.store(0, Ordering::Relaxed);
+        }
+    });
+    CONFIGURED.store(true, Ordering::Release);
+}
+
+/// Sets how often generated checks run for the given type, overriding `set_sampling`
+pub fn set_type_sampling<T: ?Sized>(sampling: Sampling) {
+    let sampler = registered(type_name::<T>());
+    sampler.sampling.store(encode(sampling), Ordering::Relaxed);
+    sampler.calls.store(0, Ordering::Relaxed);
+    CONFIGURED.store(true, Ordering::Release);
+}
+
+/// Returns true if a generated check of the given value's representation should run
+///
+/// This is what the code generated by the macros calls before checking representation. Explicit
+/// calls to `check_rep` are not sampled. Until sampling is configured with `set_sampling` or
+/// `set_type_sampling`, every check runs and nothing is counted. Afterwards, whether the check
+/// runs or is skipped is counted in `stats`. Neither takes a lock, except the first time a thread
+/// checks a type.
+pub fn should_check<T: ?Sized>(_value: &T) -> bool {
+    if !CONFIGURED.load(Ordering::Acquire) {
+        return true;
+    }
+
+    let sampler = type_sampler(type_name::<T>());
+    let sampling = match sampler.sampling.load(Ordering::Relaxed) {
+        UNSET => GLOBAL.load(Ordering::Relaxed),
+        sampling => sampling,
+    };
+    let parameter = sampling & PARAMETER;
+    let run = match sampling >> KIND_SHIFT {
+        EVERY_NTH => sampler.calls.fetch_add(1, Ordering::Relaxed).is_multiple_of(parameter),
+        PROBABILITY => next_random() < parameter,
+        _ => true,
+    };
+
+    if run {
+        sampler.executed.fetch_add(1, Ordering::Relaxed);
+    } else {
+        sampler.skipped.fetch_add(1, Ordering::Relaxed);
+    }
+    run
+}
+
+/// Returns how many generated checks ran or were skipped since sampling was configured, by type
+/// name
+pub fn stats() -> HashMap<&'static str, CheckStats> {
+    with_types(|types| {
+        types
+            .iter()
+            .map(|(name, sampler)| {
+                let stats = CheckStats {
+                    executed: sampler.executed.load(Ordering::Relaxed),
+                    skipped: sampler.skipped.load(Ordering::Relaxed),
+                };
+                (*name, stats)
+            })
+            .filter(|(_, stats)| *stats != CheckStats::default())
+            .collect()
+    })
+}
+
+/// Resets the counts returned by `stats`
+///
+/// Which checks run with `Sampling::EveryNth` is not affected.
+pub fn reset_stats() {
+    with_types(|types| {
+        for sampler in types.values() {
+            sampler.executed.store(0, Ordering::Relaxed);
+            sampler.skipped.store(0, Ordering::Relaxed);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // tests that reset stats would change the counts of the others
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn run<T>(value: &T, calls: usize) -> Vec<bool> {
+        (0..calls).map(|_| should_check(value)).collect()
+    }
+
+    #[test]
+    fn every_nth() {
+        let _serial = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        struct Sampled;
+        set_type_sampling::<Sampled>(Sampling::EveryNth(3));
+
+        let runs = run(&Sampled, 7);
+        assert_eq!(runs, vec![true, false, false, true, false, false, true]);
+        assert_eq!(stats()[type_name::<Sampled>()], CheckStats { executed: 3, skipped: 4 });
+    }
+
+    #[test]
+    fn every_nth_of_zero_runs_every_check() {
+        let _serial = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        struct Sampled;
+        set_type_sampling::<Sampled>(Sampling::EveryNth(0));
+
+        assert!(run(&Sampled, 4).into_iter().all(|run| run));
+        assert_eq!(stats()[type_name::<Sampled>()], CheckStats { executed: 4, skipped: 0 });
+    }
+
+    #[test]
+    fn every_nth_across_threads() {
+        let _serial = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        struct Sampled;
+        set_type_sampling::<Sampled>(Sampling::EveryNth(4));
+
+        let threads: Vec<_> = (0..4).map(|_| std::thread::spawn(|| run(&Sampled, 100))).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(stats()[type_name::<Sampled>()], CheckStats { executed: 100, skipped: 300 });
+    }
+
+    #[test]
+    fn reset_stats_keeps_every_nth_phase() {
+        let _serial = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        struct Sampled;
+        set_type_sampling::<Sampled>(Sampling::EveryNth(3));
+
+        assert_eq!(run(&Sampled, 2), vec![true, false]);
+        reset_stats();
+        assert!(!stats().contains_key(type_name::<Sampled>()));
+        assert_eq!(run(&Sampled, 2), vec![false, true]);
+        assert_eq!(stats()[type_name::<Sampled>()], CheckStats { executed: 1, skipped: 1 });
+    }
+
+    #[test]
+    fn probability() {
+        let _serial = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        struct Never;
+        struct Certain;
+        set_type_sampling::<Never>(Sampling::Probability(0.0));
+        set_type_sampling::<Certain>(Sampling::Probability(1.0));
+
+        assert!(run(&Never, 5).into_iter().all(|run| !run));
+        assert!(run(&Certain, 5).into_iter().all(|run| run));
+        let stats = stats();
+        assert_eq!(stats[type_name::<Never>()], CheckStats { executed: 0, skipped: 5 });
+        assert_eq!(stats[type_name::<Certain>()], CheckStats { executed: 5, skipped: 0 });
+    }
+
+    #[test]
+    fn probability_is_roughly_followed() {
+        let _serial = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        struct Sampled;
+        set_type_sampling::<Sampled>(Sampling::Probability(0.25));
+
+        let executed = run(&Sampled, 10000).into_iter().filter(|run| *run).count();
+        assert!((2000..3000).contains(&executed), "{} of 10000 checks ran", executed);
+    }
+}
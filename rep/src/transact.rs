@@ -0,0 +1,71 @@
+use crate::{handle_violations, CheckRep, Violations};
+
+/// Mutates a value with the given function, undoing the mutation if it breaks representation
+///
+/// The value is cloned before it is mutated. If its representation is incorrect afterwards, the
+/// clone is put back and the violations found are returned. Otherwise the function's return value
+/// is returned.
+pub fn transact<T, R, F>(value: &mut T, f: F) -> Result<R, Violations>
+where
+    T: CheckRep + Clone,
+    F: FnOnce(&mut T) -> R,
+{
+    let snapshot = value.clone();
+    let ret = f(value);
+
+    match value.violations() {
+        Ok(()) => Ok(ret),
+        Err(violations) => {
+            *value = snapshot;
+            Err(violations)
+        }
+    }
+}
+
+/// Puts back a snapshot of a value if its representation is incorrect, then handles the violations
+///
+/// This is what `#[check_rep(rollback)]` calls at the end of a method.
+pub fn rollback_if_incorrect<T: CheckRep>(value: &mut T, snapshot: T) {
+    if let Err(violations) = value.violations() {
+        *value = snapshot;
+        handle_violations(&violations);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::Limits;
+
+    #[test]
+    fn transact_keeps_correct_mutation() {
+        let mut limits = Limits { lo: 0, hi: 10 };
+        assert_eq!(transact(&mut limits, |l| std::mem::replace(&mut l.hi, 5)).unwrap(), 10);
+        assert_eq!(limits, Limits { lo: 0, hi: 5 });
+    }
+
+    #[test]
+    fn transact_undoes_incorrect_mutation() {
+        let mut limits = Limits { lo: 0, hi: 10 };
+        let violations = transact(&mut limits, |l| l.lo = 11).unwrap_err();
+        assert_eq!(violations.to_strings(), vec!["lo must be <= hi, not 11 > 10"]);
+        assert_eq!(limits, Limits { lo: 0, hi: 10 });
+    }
+
+    #[test]
+    fn rollback_keeps_correct_value() {
+        let mut limits = Limits { lo: 1, hi: 2 };
+        rollback_if_incorrect(&mut limits, Limits { lo: 0, hi: 0 });
+        assert_eq!(limits, Limits { lo: 1, hi: 2 });
+    }
+
+    #[test]
+    fn rollback_puts_back_snapshot_before_handling() {
+        let mut limits = Limits { lo: 3, hi: 2 };
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            rollback_if_incorrect(&mut limits, Limits { lo: 0, hi: 0 });
+        }));
+        assert!(result.is_err());
+        assert_eq!(limits, Limits { lo: 0, hi: 0 });
+    }
+}
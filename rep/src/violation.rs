@@ -0,0 +1,258 @@
+use std::error::Error;
+use std::fmt;
+
+/// A representation invariant that does not hold
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// Name of the type whose invariant does not hold, as given by `std::any::type_name`
+    pub type_name: String,
+    /// Name of the active variant if the type is an enum
+    pub variant: Option<String>,
+    /// Path to the offending value, such as `self.x`
+    pub path: String,
+    /// Kind of invariant that does not hold, such as `assert_gt`
+    pub rule: String,
+    /// What the invariant expects, such as `> 0`, where `self` refers to the value at `path`
+    pub expected: Option<String>,
+    /// The offending value
+    pub actual: Option<String>,
+    /// A description of what went wrong
+    pub message: String,
+}
+
+impl Violation {
+    /// Creates a violation of a custom invariant of the given type that is described by a message
+    pub fn custom(type_name: &str, message: String) -> Self {
+        Self {
+            type_name: String::from(type_name),
+            variant: None,
+            path: String::from("self"),
+            rule: String::from("custom"),
+            expected: None,
+            actual: None,
+            message,
+        }
+    }
+}
+
+impl Violation {
+    /// Makes this violation, found in a value at the given path, relative to the containing value
+    ///
+    /// The path of the violation, which starts with `self`, is joined to the given path, so a
+    /// violation at `self.x` found in `self.position` ends up at `self.position.x`. The path may
+    /// also index into collections, as in `self.points[3]` or `self.scores["alice"]`. A message
+    /// that starts with the path, as generated messages do, is made to start with the new path.
+    /// The rest of the message and the expected value are left as they are, so they may still
+    /// refer to the value at the path as `self`.
+    pub fn within(&mut self, path: &str) {
+        let joined = join(path, &self.path);
+        if let Some(rest) = self.message.strip_prefix(self.path.as_str()) {
+            if rest.is_empty() || rest.starts_with(' ') {
+                self.message = format!("{}{}", joined, rest);
+            }
+        }
+        self.path = joined;
+    }
+}
+
+/// Joins a path that starts with `self` to the path of the value that `self` refers to
+fn join(path: &str, relative: &str) -> String {
+    match relative.strip_prefix("self") {
+        Some(rest) if rest.is_empty() || rest.starts_with('.') || rest.starts_with('[') => format!("{}{}", path, rest),
+        _ => format!("{}.{}", path, relative),
+    }
+}
+
+/// The message is preceded by the path if it doesn't mention it, such as the message of an
+/// invariant of a nested value
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(variant) = &self.variant {
+            write!(f, "{}::{}: ", self.type_name, variant)?;
+        }
+        if self.path != "self" && !self.message.starts_with(self.path.as_str()) {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// A collection of representation invariants that do not hold
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Violations {
+    violations: Vec<Violation>,
+}
+
+impl Violations {
+    /// Creates an empty collection of violations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates violations of custom invariants of the given type from their messages
+    ///
+    /// This is how the messages returned by hand-written implementations of `correctness` are
+    /// turned into violations. If there are no messages, a single violation is still created.
+    pub fn from_messages(type_name: &str, messages: Vec<String>) -> Self {
+        if messages.is_empty() {
+            Violation::custom(type_name, String::from("representation invariant violated")).into()
+        } else {
+            messages.into_iter().map(|message| Violation::custom(type_name, message)).collect()
+        }
+    }
+
+    /// Adds a violation
+    pub fn push(&mut self, violation: Violation) {
+        self.violations.push(violation);
+    }
+
+    /// Moves all violations of `other` into `self`
+    pub fn append(&mut self, other: &mut Violations) {
+        self.violations.append(&mut other.violations);
+    }
+
+    /// Returns the number of violations
+    pub fn len(&self) -> usize {
+        self.violations.len()
+    }
+
+    /// Returns true if there are no violations
+    pub fn is_empty(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Returns an iterator over the violations
+    pub fn iter(&self) -> std::slice::Iter<'_, Violation> {
+        self.violations.iter()
+    }
+
+    /// Returns an iterator that allows modifying each violation
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Violation> {
+        self.violations.iter_mut()
+    }
+
+    /// Makes all violations, found in a value at the given path, relative to the containing value
+    ///
+    /// See [`Violation::within`](struct.Violation.html#method.within).
+    pub fn within(&mut self, path: &str) {
+        for violation in &mut self.violations {
+            violation.within(path);
+        }
+    }
+
+    /// Returns a message for each violation
+    pub fn to_strings(&self) -> Vec<String> {
+        self.violations.iter().map(|violation| violation.to_string()).collect()
+    }
+}
+
+impl From<Violation> for Violations {
+    fn from(violation: Violation) -> Self {
+        Self {
+            violations: vec![violation],
+        }
+    }
+}
+
+impl std::iter::FromIterator<Violation> for Violations {
+    fn from_iter<I: IntoIterator<Item = Violation>>(iter: I) -> Self {
+        Self {
+            violations: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl IntoIterator for Violations {
+    type Item = Violation;
+    type IntoIter = std::vec::IntoIter<Violation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.violations.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Violations {
+    type Item = &'a Violation;
+    type IntoIter = std::slice::Iter<'a, Violation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.violations.iter()
+    }
+}
+
+impl fmt::Display for Violations {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, violation) in self.violations.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for Violations {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn violation(path: &str, message: &str, expected: &str) -> Violation {
+        Violation {
+            type_name: String::from("Named"),
+            variant: None,
+            path: String::from(path),
+            rule: String::from("assert_eq"),
+            expected: Some(String::from(expected)),
+            actual: None,
+            message: String::from(message),
+        }
+    }
+
+    #[test]
+    fn within_joins_paths() {
+        let mut v = violation("self.name", "self.name must be good, not myself is self", "== self");
+        v.within("self.inner");
+        assert_eq!(v.path, "self.inner.name");
+        assert_eq!(v.message, "self.inner.name must be good, not myself is self");
+        assert_eq!(v.expected.as_deref(), Some("== self"));
+
+        v.within("self[\"self\"]");
+        assert_eq!(v.path, "self[\"self\"].inner.name");
+        assert_eq!(v.message, "self[\"self\"].inner.name must be good, not myself is self");
+    }
+
+    #[test]
+    fn within_leaves_other_messages_alone() {
+        let mut v = Violation::custom("Limits", String::from("self.lo <= self.hi must be true"));
+        assert_eq!(v.to_string(), "self.lo <= self.hi must be true");
+
+        v.within("self.limits");
+        assert_eq!(v.path, "self.limits");
+        assert_eq!(v.message, "self.lo <= self.hi must be true");
+        assert_eq!(v.to_string(), "self.limits: self.lo <= self.hi must be true");
+    }
+
+    #[test]
+    fn display_includes_variant() {
+        let mut v = violation("self.n", "self.n must be > 0, not 0", "> 0");
+        v.variant = Some(String::from("Many"));
+        assert_eq!(v.to_string(), "Named::Many: self.n must be > 0, not 0");
+    }
+
+    #[test]
+    fn violations_within() {
+        let mut violations: Violations = vec![
+            violation("self", "self must be good, not bad", "== good"),
+            violation("self.x", "self.x must be good, not bad", "== good"),
+        ]
+        .into_iter()
+        .collect();
+        violations.within("self[2]");
+        assert_eq!(
+            violations.to_strings(),
+            vec!["self[2] must be good, not bad", "self[2].x must be good, not bad"]
+        );
+    }
+}
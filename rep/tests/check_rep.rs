@@ -0,0 +1,145 @@
+// checks can't be observed when they are compiled out
+#![cfg(not(any(feature = "off", all(feature = "debug-only", not(debug_assertions)))))]
+
+mod common;
+
+use common::{handled, messages, Limits};
+use rep::*;
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+#[check_rep]
+impl Limits {
+    pub fn set_lo(&mut self, lo: i32) {
+        self.lo = lo;
+    }
+
+    pub fn set_hi(&mut self, #[rep(assert_ge = 0)] hi: i32) -> Result<(), String> {
+        if hi > 1000 {
+            return Err(String::from("too high"));
+        }
+        self.hi = hi;
+        Ok(())
+    }
+
+    pub fn parse_hi(&mut self, hi: &str) -> Result<(), std::num::ParseIntError> {
+        self.hi = -1;
+        self.hi = hi.parse()?;
+        Ok(())
+    }
+
+    pub fn copy_from(&mut self, #[rep(check)] other: &Limits) {
+        self.lo = other.lo;
+        self.hi = other.hi;
+    }
+
+    pub fn lo_mut(&mut self) -> &mut i32 {
+        &mut self.lo
+    }
+
+    pub async fn reload(&mut self, lo: i32) {
+        self.lo = lo;
+    }
+}
+
+#[check_rep(rollback)]
+impl Limits {
+    pub fn set_hi_or_undo(&mut self, hi: i32) {
+        self.hi = hi;
+    }
+}
+
+#[check_rep(on_unwind)]
+impl Limits {
+    pub fn set_lo_then_panic(&mut self, lo: i32) {
+        self.lo = lo;
+        panic!("gave up");
+    }
+}
+
+/// Polls a future that never waits to completion
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    match Pin::as_mut(&mut future).poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("future is pending"),
+    }
+}
+
+#[test]
+fn checked_on_every_exit() {
+    let mut limits = Limits { lo: 0, hi: 10 };
+    assert!(handled(|| limits.set_lo(5)).is_empty());
+    assert_eq!(messages(&handled(|| limits.set_lo(11))), vec!["lo must not exceed hi"]);
+
+    let mut limits = Limits { lo: 0, hi: 10 };
+    let mut result = Ok(());
+    assert!(handled(|| result = limits.set_hi(5000)).is_empty());
+    assert_eq!(result, Err(String::from("too high")));
+
+    let mut result = Ok(());
+    assert_eq!(messages(&handled(|| result = limits.parse_hi("x"))), vec!["lo must not exceed hi"]);
+    assert!(result.is_err());
+}
+
+#[test]
+fn arguments_are_checked() {
+    let mut limits = Limits { lo: 0, hi: 10 };
+    let violations = handled(|| limits.set_hi(-1));
+    assert_eq!(
+        messages(&violations),
+        vec!["hi must be >= 0, not -1 in call to set_hi", "lo must not exceed hi"]
+    );
+    assert_eq!(violations[0].path, "hi");
+
+    let mut limits = Limits { lo: 0, hi: 10 };
+    let violations = handled(|| limits.copy_from(&Limits { lo: 2, hi: 1 }));
+    assert_eq!(
+        messages(&violations),
+        vec!["other: lo must not exceed hi in call to copy_from", "lo must not exceed hi"]
+    );
+}
+
+#[test]
+fn borrows_of_self_are_checked_at_start() {
+    let mut limits = Limits { lo: 0, hi: 10 };
+    assert!(handled(|| *limits.lo_mut() = 20).is_empty());
+    assert_eq!(messages(&handled(|| limits.lo_mut())), vec!["lo must not exceed hi"]);
+}
+
+#[test]
+fn async_methods_are_checked_at_start() {
+    let mut limits = Limits { lo: 0, hi: 10 };
+    assert!(handled(|| block_on(limits.reload(20))).is_empty());
+    assert_eq!(messages(&handled(|| block_on(limits.reload(0)))), vec!["lo must not exceed hi"]);
+    assert_eq!(limits, Limits { lo: 0, hi: 10 });
+}
+
+#[test]
+fn rollback() {
+    let mut limits = Limits { lo: 0, hi: 10 };
+    assert!(handled(|| limits.set_hi_or_undo(5)).is_empty());
+    assert_eq!(limits, Limits { lo: 0, hi: 5 });
+
+    assert_eq!(messages(&handled(|| limits.set_hi_or_undo(-1))), vec!["lo must not exceed hi"]);
+    assert_eq!(limits, Limits { lo: 0, hi: 5 });
+}
+
+#[test]
+fn checked_when_a_method_panics() {
+    let mut limits = Limits { lo: 0, hi: 10 };
+    let mut payload = None;
+    let violations = handled(|| {
+        payload = catch_unwind(AssertUnwindSafe(|| limits.set_lo_then_panic(11))).err();
+    });
+    assert_eq!(
+        messages(&violations),
+        vec!["lo must not exceed hi (left behind by a method that panicked)"]
+    );
+    assert_eq!(payload.unwrap().downcast_ref::<&str>(), Some(&"gave up"));
+
+    limits.lo = 0;
+    assert!(handled(|| catch_unwind(AssertUnwindSafe(|| limits.set_lo_then_panic(5)))).is_empty());
+}
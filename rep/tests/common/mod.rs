@@ -0,0 +1,36 @@
+use rep::{set_violation_handler, CheckRep, Violation, ViolationHandler};
+use std::cell::RefCell;
+use std::sync::Once;
+
+thread_local! {
+    static VIOLATIONS: RefCell<Vec<Violation>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Runs the given function and returns the violations it handled, in the current thread
+pub fn handled<R, F: FnOnce() -> R>(f: F) -> Vec<Violation> {
+    static HANDLER: Once = Once::new();
+    HANDLER.call_once(|| {
+        set_violation_handler(ViolationHandler::custom(|violations| {
+            VIOLATIONS.with(|handled| handled.borrow_mut().extend(violations.iter().cloned()));
+        }));
+    });
+
+    VIOLATIONS.with(|handled| handled.borrow_mut().clear());
+    f();
+    VIOLATIONS.with(|handled| handled.borrow_mut().drain(..).collect())
+}
+
+/// Returns the messages of the given violations, as they are displayed
+#[allow(dead_code)]
+pub fn messages(violations: &[Violation]) -> Vec<String> {
+    violations.iter().map(|violation| violation.to_string()).collect()
+}
+
+/// A pair of bounds whose representation is correct when `lo <= hi`
+#[allow(dead_code)]
+#[derive(CheckRep, Debug, Clone, PartialEq)]
+#[rep(invariant = "self.lo <= self.hi", message = "lo must not exceed hi")]
+pub struct Limits {
+    pub lo: i32,
+    pub hi: i32,
+}
@@ -0,0 +1,72 @@
+// checks can't be observed when they are compiled out
+#![cfg(not(any(feature = "off", all(feature = "debug-only", not(debug_assertions)))))]
+
+mod common;
+
+use common::{handled, messages};
+use rep::*;
+
+#[derive(CheckRep, Debug)]
+struct Radius {
+    #[rep(assert_gt = 0)]
+    r: i32,
+}
+
+#[check_rep]
+impl Radius {
+    pub fn new(r: i32) -> Self {
+        Radius { r }
+    }
+
+    pub fn parse(r: &str) -> Result<Self, std::num::ParseIntError> {
+        Ok(Radius { r: r.parse()? })
+    }
+
+    pub fn checked(r: i32) -> Option<Radius> {
+        if r == 0 {
+            None
+        } else {
+            Some(Radius { r })
+        }
+    }
+
+    pub async fn load(r: i32) -> Self {
+        Radius { r }
+    }
+}
+
+impl Radius {
+    #[construct_rep]
+    fn unit(r: i32) -> Self {
+        Radius { r }
+    }
+
+    #[construct_rep(debug)]
+    fn debug_only(r: i32) -> Self {
+        Radius { r }
+    }
+}
+
+#[test]
+fn constructors_of_impl_block() {
+    assert!(handled(|| Radius::new(1)).is_empty());
+    assert_eq!(messages(&handled(|| Radius::new(-1))), vec!["self.r must be > 0, not -1"]);
+    assert_eq!(messages(&handled(|| Radius::parse("-2"))), vec!["self.r must be > 0, not -2"]);
+    assert!(handled(|| Radius::parse("x")).is_empty());
+    assert_eq!(messages(&handled(|| Radius::checked(-3))), vec!["self.r must be > 0, not -3"]);
+    assert!(handled(|| Radius::checked(0)).is_empty());
+    // the value an async constructor returns isn't checked
+    assert!(handled(|| Radius::load(-4)).is_empty());
+}
+
+#[test]
+fn construct_rep() {
+    assert_eq!(messages(&handled(|| Radius::unit(0))), vec!["self.r must be > 0, not 0"]);
+
+    let debug_only = handled(|| Radius::debug_only(0));
+    if cfg!(debug_assertions) {
+        assert_eq!(messages(&debug_only), vec!["self.r must be > 0, not 0"]);
+    } else {
+        assert!(debug_only.is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+// checks can't be observed when they are compiled out
+#![cfg(not(any(feature = "off", all(feature = "debug-only", not(debug_assertions)))))]
+
+mod common;
+
+use common::{handled, messages};
+use rep::*;
+
+#[derive(Debug, Clone)]
+struct Stack {
+    items: Vec<i32>,
+}
+
+impl Stack {
+    #[ensures("self.items.len() == old(self.items.len()) + 1")]
+    fn push(&mut self, item: i32) {
+        self.items.push(item);
+        if item < 0 {
+            self.items.push(item);
+        }
+    }
+
+    #[requires("idx < self.items.len()")]
+    fn get(&self, idx: usize) -> Option<&i32> {
+        self.items.get(idx)
+    }
+
+    #[ensures("ret.len() == self.items.len()")]
+    #[ensures("(0..ret.len()).all(|i| ret[i] >= 0)")]
+    fn positive(&self) -> Vec<i32> {
+        self.items.clone()
+    }
+
+    #[ensures_ret(assert_ge = 0, assert_le = 10)]
+    fn sum(&self) -> i32 {
+        if self.items.is_empty() {
+            return 0;
+        }
+        self.items.iter().sum()
+    }
+}
+
+// a field named ret doesn't refer to the returned value
+#[derive(Debug, Clone)]
+struct Counter {
+    ret: u32,
+}
+
+impl Counter {
+    #[ensures("self.ret == old(self.ret) + 1")]
+    fn bump(&mut self, by: u32) {
+        self.ret += by;
+    }
+}
+
+#[test]
+fn requires() {
+    let stack = Stack { items: vec![1] };
+    assert!(handled(|| stack.get(0)).is_empty());
+
+    let violations = handled(|| stack.get(1));
+    assert_eq!(messages(&violations), vec!["idx < self.items.len() must be true on entry to get"]);
+    assert_eq!(violations[0].rule, "requires");
+}
+
+#[test]
+fn ensures_with_old() {
+    let mut stack = Stack { items: vec![] };
+    assert!(handled(|| stack.push(1)).is_empty());
+
+    let violations = handled(|| stack.push(-1));
+    assert_eq!(
+        messages(&violations),
+        vec!["self.items.len() == old(self.items.len()) + 1 must be true on exit from push"]
+    );
+    assert_eq!(violations[0].rule, "ensures");
+}
+
+#[test]
+fn ensures_with_ret() {
+    let stack = Stack { items: vec![1, -2] };
+    assert_eq!(
+        messages(&handled(|| stack.positive())),
+        vec!["(0..ret.len()).all(|i| ret[i] >= 0) must be true on exit from positive"]
+    );
+}
+
+#[test]
+fn ensures_ret() {
+    assert!(handled(|| Stack { items: vec![] }.sum()).is_empty());
+    assert!(handled(|| Stack { items: vec![4, 6] }.sum()).is_empty());
+
+    let violations = handled(|| Stack { items: vec![5, 10] }.sum());
+    assert_eq!(messages(&violations), vec!["ret must be <= 10, not 15 on exit from sum"]);
+    assert_eq!(violations[0].path, "ret");
+    assert_eq!(violations[0].rule, "assert_le");
+}
+
+#[test]
+fn ensures_with_field_named_ret() {
+    let mut counter = Counter { ret: 0 };
+    assert!(handled(|| counter.bump(1)).is_empty());
+    assert_eq!(messages(&handled(|| counter.bump(2))), vec!["self.ret == old(self.ret) + 1 must be true on exit from bump"]);
+}
@@ -0,0 +1,241 @@
+use rep::*;
+use std::collections::{BTreeMap, HashMap};
+
+// overrides only `violations`, which derived checks of fields must go through
+#[derive(Debug)]
+struct Odd(u32);
+
+impl CheckRep for Odd {
+    fn violations(&self) -> Result<(), Violations> {
+        if self.0 % 2 == 1 {
+            Ok(())
+        } else {
+            Err(Violation::custom("Odd", format!("{} must be odd", self.0)).into())
+        }
+    }
+}
+
+#[derive(CheckRep, Debug)]
+struct Pair {
+    #[rep(check)]
+    odd: Odd,
+}
+
+#[test]
+fn check_of_field_that_only_has_violations() {
+    assert!(Pair { odd: Odd(1) }.is_correct());
+    assert!(!Pair { odd: Odd(2) }.is_correct());
+    assert_eq!(Pair { odd: Odd(2) }.violations().unwrap_err().to_strings(), vec!["self.odd: 2 must be odd"]);
+}
+
+#[derive(CheckRep, Debug)]
+struct Five {
+    #[rep(use_custom)]
+    #[rep(assert_ge = 0)]
+    value: i32,
+}
+
+impl CustomCheckRep for Five {
+    fn c_correctness(&self) -> Result<(), Vec<String>> {
+        if self.value == 5 {
+            Err(vec![String::from("five")])
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn use_custom() {
+    assert!(Five { value: 4 }.is_correct());
+    assert!(!Five { value: 5 }.is_correct());
+    assert_eq!(Five { value: 5 }.violations().unwrap_err().to_strings(), vec!["five"]);
+    assert_eq!(Five { value: -1 }.violations().unwrap_err().to_strings(), vec!["self.value must be >= 0, not -1"]);
+}
+
+#[derive(CheckRep, Debug)]
+enum Conn {
+    Open {
+        #[rep(assert_ge = 0)]
+        fd: i32,
+    },
+    Closed,
+}
+
+#[test]
+fn enum_variants() {
+    assert!(Conn::Closed.is_correct());
+    assert!(Conn::Open { fd: 3 }.is_correct());
+
+    let violations = Conn::Open { fd: -1 }.violations().unwrap_err();
+    let violation = violations.iter().next().unwrap();
+    assert_eq!(violation.type_name, std::any::type_name::<Conn>());
+    assert_eq!(violation.variant.as_deref(), Some("Open"));
+    assert_eq!(violation.rule, "assert_ge");
+    assert_eq!(violation.to_string(), format!("{}::Open: self.fd must be >= 0, not -1", std::any::type_name::<Conn>()));
+}
+
+#[derive(CheckRep, Debug)]
+#[rep(invariant = "self.x1 != self.x2 && self.y1 != self.y2")]
+#[rep(invariant = "self.x1 <= self.x2", message = "x1 must not exceed x2")]
+struct Rect {
+    x1: i32,
+    y1: i32,
+    x2: i32,
+    y2: i32,
+}
+
+#[test]
+fn invariants() {
+    assert!(Rect { x1: 0, y1: 0, x2: 1, y2: 1 }.is_correct());
+
+    let violations = Rect { x1: 1, y1: 0, x2: 1, y2: 1 }.violations().unwrap_err();
+    assert_eq!(violations.to_strings(), vec!["self.x1 != self.x2 && self.y1 != self.y2 must be true"]);
+
+    let violations = Rect { x1: 2, y1: 0, x2: 1, y2: 1 }.violations().unwrap_err();
+    assert_eq!(violations.to_strings(), vec!["x1 must not exceed x2"]);
+    assert_eq!(violations.iter().next().unwrap().rule, "invariant");
+}
+
+#[derive(CheckRep, Debug)]
+struct Point {
+    #[rep(assert_ge = 0)]
+    x: i32,
+    #[rep(assert_ge = 0)]
+    y: i32,
+}
+
+#[derive(CheckRep, Debug)]
+struct Segment {
+    #[rep(check)]
+    start: Point,
+    #[rep(check)]
+    end: Point,
+}
+
+#[derive(CheckRep, Debug)]
+struct Path(#[rep(check)] Segment);
+
+#[test]
+fn nested_paths() {
+    let path = Path(Segment { start: Point { x: -1, y: 0 }, end: Point { x: 0, y: -2 } });
+    let violations = path.violations().unwrap_err();
+
+    assert_eq!(
+        violations.to_strings(),
+        vec!["self.0.start.x must be >= 0, not -1", "self.0.end.y must be >= 0, not -2"]
+    );
+    let paths: Vec<&str> = violations.iter().map(|violation| violation.path.as_str()).collect();
+    assert_eq!(paths, vec!["self.0.start.x", "self.0.end.y"]);
+    assert_eq!(violations.iter().next().unwrap().type_name, std::any::type_name::<Point>());
+}
+
+#[derive(CheckRep, Debug, PartialEq)]
+#[rep(invariant = "self.lo <= self.hi", message = "lo must not exceed hi")]
+#[rep(constructor)]
+struct Interval {
+    #[rep(assert_ge = 0)]
+    lo: i32,
+    hi: i32,
+}
+
+#[test]
+fn try_new() {
+    assert_eq!(Interval::try_new(1, 2).unwrap(), Interval { lo: 1, hi: 2 });
+    assert_eq!(Interval::try_new(2, 1).unwrap_err().to_strings(), vec!["lo must not exceed hi"]);
+    assert_eq!(
+        Interval::try_new(-1, -2).unwrap_err().to_strings(),
+        vec!["self.lo must be >= 0, not -1", "lo must not exceed hi"]
+    );
+}
+
+#[derive(CheckRep, Debug)]
+struct LatLong {
+    #[rep(range = "-90.0..=90.0")]
+    lat: f64,
+    #[rep(range = "-180.0..180.0")]
+    long: f64,
+    #[rep(range = "1..")]
+    zoom: u8,
+}
+
+#[test]
+fn ranges() {
+    assert!(LatLong { lat: 90.0, long: -180.0, zoom: 1 }.is_correct());
+
+    let violations = LatLong { lat: 91.0, long: 180.0, zoom: 0 }.violations().unwrap_err();
+    assert_eq!(
+        violations.to_strings(),
+        vec![
+            "self.lat must be in -90.0..=90.0, not 91.0",
+            "self.long must be in -180.0..180.0, not 180.0",
+            "self.zoom must be in 1.., not 0",
+        ]
+    );
+    assert_eq!(violations.iter().next().unwrap().rule, "range");
+}
+
+#[derive(CheckRep, Debug)]
+struct Account {
+    #[rep(non_empty)]
+    name: String,
+    #[rep(len = 4)]
+    pin: Vec<u8>,
+    #[rep(min_len = 1, max_len = 2)]
+    roles: HashMap<String, u8>,
+}
+
+#[test]
+fn lengths() {
+    let roles: HashMap<_, _> = vec![(String::from("admin"), 1)].into_iter().collect();
+    assert!(Account { name: String::from("alice"), pin: vec![1, 2, 3, 4], roles }.is_correct());
+
+    let account = Account { name: String::new(), pin: vec![1, 2], roles: Default::default() };
+    let violations = account.violations().unwrap_err();
+    assert_eq!(
+        violations.to_strings(),
+        vec![
+            "self.name must not be empty",
+            "self.pin must have a len of 4, not 2",
+            "self.roles must have a len >= 1, not 0",
+        ]
+    );
+    let rules: Vec<&str> = violations.iter().map(|violation| violation.rule.as_str()).collect();
+    assert_eq!(rules, vec!["non_empty", "len", "min_len"]);
+
+    let roles = (0..3).map(|i| (i.to_string(), i)).collect();
+    let account = Account { name: String::from("bob"), pin: vec![1, 2, 3, 4], roles };
+    assert_eq!(account.violations().unwrap_err().to_strings(), vec!["self.roles must have a len <= 2, not 3"]);
+}
+
+#[derive(CheckRep, Debug)]
+struct Scene {
+    #[rep(each(check))]
+    points: Vec<Point>,
+    #[rep(each(assert_gt = 0))]
+    sizes: [u32; 3],
+    #[rep(keys(non_empty), values(range = "0..=100"))]
+    scores: BTreeMap<String, u8>,
+}
+
+#[test]
+fn element_rules() {
+    let scene = Scene {
+        points: vec![Point { x: 0, y: 0 }, Point { x: 0, y: -2 }],
+        sizes: [1, 0, 2],
+        scores: vec![(String::new(), 50), (String::from("alice"), 150)].into_iter().collect(),
+    };
+    let violations = scene.violations().unwrap_err();
+
+    assert_eq!(
+        violations.to_strings(),
+        vec![
+            "self.points[1].y must be >= 0, not -2",
+            "self.sizes[1] must be > 0, not 0",
+            "self.scores[\"\"] must not be empty",
+            "self.scores[\"alice\"] must be in 0..=100, not 150",
+        ]
+    );
+    let paths: Vec<&str> = violations.iter().map(|violation| violation.path.as_str()).collect();
+    assert_eq!(paths, vec!["self.points[1].y", "self.sizes[1]", "self.scores[\"\"]", "self.scores[\"alice\"]"]);
+}
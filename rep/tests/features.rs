@@ -0,0 +1,58 @@
+// each feature is tested by running the tests with it, in builds with and without debug assertions
+
+mod common;
+
+use common::{handled, Limits};
+use rep::*;
+
+#[check_rep]
+impl Limits {
+    pub fn set_lo(&mut self, lo: i32) {
+        self.lo = lo;
+    }
+}
+
+#[check_rep(debug)]
+impl Limits {
+    pub fn set_lo_in_debug(&mut self, lo: i32) {
+        self.lo = lo;
+    }
+}
+
+#[cfg(feature = "off")]
+#[test]
+fn off_disables_checks() {
+    assert!(!checks_enabled(true));
+    assert!(!checks_enabled(false));
+}
+
+#[cfg(all(feature = "debug-only", not(feature = "off")))]
+#[test]
+fn debug_only_enables_checks_with_debug_assertions() {
+    assert!(checks_enabled(true));
+    assert!(!checks_enabled(false));
+}
+
+#[cfg(not(any(feature = "off", feature = "debug-only")))]
+#[test]
+fn always_enables_checks() {
+    assert!(checks_enabled(true));
+    assert!(checks_enabled(false));
+}
+
+#[test]
+fn generated_checks_follow_features() {
+    let mut limits = Limits { lo: 0, hi: 10 };
+    let checked = !handled(|| limits.set_lo(11)).is_empty();
+    assert_eq!(checked, checks_enabled(cfg!(debug_assertions)));
+
+    let limits = Limits { lo: 11, hi: 10 };
+    assert_eq!(handled(|| limits.check_rep()).is_empty(), !checks_enabled(cfg!(debug_assertions)));
+}
+
+#[test]
+fn debug_checks_need_debug_assertions() {
+    let mut limits = Limits { lo: 0, hi: 10 };
+    let checked = !handled(|| limits.set_lo_in_debug(11)).is_empty();
+    assert_eq!(checked, cfg!(debug_assertions) && checks_enabled(true));
+}
@@ -0,0 +1,71 @@
+use rep::*;
+
+#[derive(CheckRep, Debug)]
+struct Reading {
+    #[rep(finite, assert_gt = 0.0)]
+    value: f64,
+    #[rep(not_nan)]
+    offset: f32,
+    #[rep(normalized)]
+    direction: Vec<f64>,
+    #[rep(approx_eq = 1.0, epsilon = 0.5)]
+    gain: f64,
+    // the epsilon applies to normalized, not to the rule after it
+    #[rep(normalized, epsilon = 0.1, len = 2)]
+    rough: [f32; 2],
+}
+
+impl Default for Reading {
+    fn default() -> Self {
+        Reading { value: 1.0, offset: 0.0, direction: vec![0.6, 0.8], gain: 1.2, rough: [1.05, 0.0] }
+    }
+}
+
+fn messages(reading: Reading) -> Vec<String> {
+    match reading.violations() {
+        Ok(()) => vec![],
+        Err(violations) => violations.to_strings(),
+    }
+}
+
+#[test]
+fn correct() {
+    assert!(messages(Reading::default()).is_empty());
+}
+
+#[test]
+fn finite() {
+    let reading = Reading { value: f64::INFINITY, ..Reading::default() };
+    assert_eq!(messages(reading), vec!["self.value must be finite, not inf"]);
+
+    let reading = Reading { value: f64::NAN, ..Reading::default() };
+    assert_eq!(messages(reading)[0], "self.value must be finite, not NaN");
+}
+
+#[test]
+fn not_nan() {
+    let reading = Reading { offset: f32::NAN, ..Reading::default() };
+    assert_eq!(messages(reading), vec!["self.offset must not be NaN"]);
+
+    let reading = Reading { offset: f32::INFINITY, ..Reading::default() };
+    assert!(messages(reading).is_empty());
+}
+
+#[test]
+fn normalized() {
+    let reading = Reading { direction: vec![0.6, 0.81], ..Reading::default() };
+    let found = messages(reading);
+    assert_eq!(found.len(), 1);
+    assert!(found[0].starts_with("self.direction must have a norm of 1 ± 0.000001, not 1.00"), "{}", found[0]);
+
+    let reading = Reading { rough: [1.2, 0.0], ..Reading::default() };
+    let found = messages(reading);
+    assert_eq!(found.len(), 1);
+    assert!(found[0].starts_with("self.rough must have a norm of 1 ± 0.1, not 1.2"), "{}", found[0]);
+}
+
+#[test]
+fn approx_eq() {
+    let reading = Reading { gain: 1.6, ..Reading::default() };
+    assert_eq!(messages(reading), vec!["self.gain must be 1 ± 0.5, not 1.6"]);
+}
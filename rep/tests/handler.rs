@@ -0,0 +1,47 @@
+// checks can't be observed when they are compiled out
+#![cfg(not(any(feature = "off", all(feature = "debug-only", not(debug_assertions)))))]
+
+use rep::*;
+use std::panic::catch_unwind;
+use std::sync::{Arc, Mutex};
+
+#[derive(CheckRep, Debug)]
+struct Positive {
+    #[rep(assert_gt = 0)]
+    value: i32,
+}
+
+// the handler is global, so every handler is tested in turn
+#[test]
+fn handlers() {
+    let broken = Positive { value: 0 };
+
+    // violations panic until a handler is set
+    let payload = catch_unwind(|| broken.check_rep()).unwrap_err();
+    assert_eq!(
+        payload.downcast_ref::<String>().unwrap(),
+        "representation invariant violated: [\"self.value must be > 0, not 0\"]"
+    );
+
+    set_violation_handler(ViolationHandler::Count);
+    reset_violation_count();
+    broken.check_rep();
+    broken.check_rep();
+    Positive { value: 1 }.check_rep();
+    assert_eq!(violation_count(), 2);
+    reset_violation_count();
+    assert_eq!(violation_count(), 0);
+
+    let handled = Arc::new(Mutex::new(vec![]));
+    let sink = Arc::clone(&handled);
+    set_violation_handler(ViolationHandler::custom(move |violations| {
+        sink.lock().unwrap().extend(violations.to_strings());
+    }));
+    broken.check_rep();
+    assert_eq!(*handled.lock().unwrap(), vec!["self.value must be > 0, not 0"]);
+
+    set_violation_handler(ViolationHandler::Ignore);
+    broken.check_rep();
+    assert_eq!(handled.lock().unwrap().len(), 1);
+    assert_eq!(violation_count(), 0);
+}
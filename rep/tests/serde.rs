@@ -0,0 +1,37 @@
+#![cfg(feature = "serde")]
+
+use rep::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(CheckRep, Debug, Deserialize, Serialize)]
+struct Port {
+    #[rep(assert_gt = 0)]
+    number: i32,
+}
+
+#[derive(CheckRep, Debug, Deserialize, Serialize)]
+struct Server {
+    #[rep(check)]
+    port: Port,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Config {
+    server: Checked<Server>,
+}
+
+#[test]
+fn deserializes_correct_value() {
+    let config: Config = serde_json::from_str(r#"{ "server": { "port": { "number": 80 } } }"#).unwrap();
+    assert_eq!(config.server.port.number, 80);
+    assert_eq!(serde_json::to_string(&config).unwrap(), r#"{"server":{"port":{"number":80}}}"#);
+}
+
+#[test]
+fn error_includes_path() {
+    let error = serde_json::from_str::<Config>(r#"{ "server": { "port": { "number": 0 } } }"#).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "representation invariant violated: self.port.number must be > 0, not 0 at line 1 column 41"
+    );
+}
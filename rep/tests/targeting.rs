@@ -0,0 +1,89 @@
+// checks can't be observed when they are compiled out
+#![cfg(not(any(feature = "off", all(feature = "debug-only", not(debug_assertions)))))]
+
+mod common;
+
+use common::{handled, messages, Limits};
+use rep::*;
+
+#[check_rep(include = "pub(crate), private")]
+impl Limits {
+    pub(crate) fn crate_set_lo(&mut self, lo: i32) {
+        self.lo = lo;
+    }
+
+    fn private_set_lo(&mut self, lo: i32) {
+        self.lo = lo;
+    }
+
+    pub fn public_set_lo(&mut self, lo: i32) {
+        self.lo = lo;
+    }
+}
+
+#[check_rep(receivers = "&self, self")]
+impl Limits {
+    pub fn width(&self) -> i32 {
+        self.hi - self.lo
+    }
+
+    pub fn into_lo(self) -> i32 {
+        self.lo
+    }
+
+    pub fn unchecked_set_lo(&mut self, lo: i32) {
+        self.lo = lo;
+    }
+}
+
+#[check_rep(skip = "reset")]
+impl Limits {
+    pub fn reset(&mut self, lo: i32) {
+        self.lo = lo;
+    }
+
+    #[rep_skip]
+    pub fn raw_set_lo(&mut self, lo: i32) {
+        self.lo = lo;
+    }
+
+    pub fn checked_set_lo(&mut self, lo: i32) {
+        self.lo = lo;
+    }
+}
+
+#[test]
+fn include() {
+    let mut limits = Limits { lo: 0, hi: 10 };
+    assert_eq!(messages(&handled(|| limits.crate_set_lo(11))), vec!["lo must not exceed hi"]);
+
+    let mut limits = Limits { lo: 0, hi: 10 };
+    assert_eq!(messages(&handled(|| limits.private_set_lo(11))), vec!["lo must not exceed hi"]);
+
+    let mut limits = Limits { lo: 0, hi: 10 };
+    assert!(handled(|| limits.public_set_lo(11)).is_empty());
+}
+
+#[test]
+fn receivers() {
+    // methods that borrow self are checked at the start and on exit
+    let limits = Limits { lo: 11, hi: 10 };
+    assert_eq!(handled(|| limits.width()).len(), 2);
+
+    // methods that take self by value are only checked at the start
+    let limits = Limits { lo: 11, hi: 10 };
+    assert_eq!(messages(&handled(|| limits.into_lo())), vec!["lo must not exceed hi"]);
+
+    let mut limits = Limits { lo: 0, hi: 10 };
+    assert!(handled(|| limits.unchecked_set_lo(11)).is_empty());
+}
+
+#[test]
+fn skip() {
+    let mut limits = Limits { lo: 0, hi: 10 };
+    assert!(handled(|| limits.reset(11)).is_empty());
+    assert!(handled(|| limits.raw_set_lo(12)).is_empty());
+
+    let mut limits = Limits { lo: 0, hi: 10 };
+    assert_eq!(messages(&handled(|| limits.checked_set_lo(11))), vec!["lo must not exceed hi"]);
+}
@@ -0,0 +1,10 @@
+use rep::*;
+
+#[derive(CheckRep)]
+#[rep(invariant = "self.lo <=")]
+struct Limits {
+    lo: i32,
+    hi: i32,
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use rep::*;
+
+#[derive(CheckRep)]
+struct Limits {
+    lo: i32,
+}
+
+#[check_rep(include = "pub, public")]
+impl Limits {
+    pub fn set_lo(&mut self, lo: i32) {
+        self.lo = lo;
+    }
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use rep::*;
+
+#[derive(CheckRep)]
+struct Percent {
+    #[rep(range = "0..0")]
+    value: u8,
+}
+
+fn main() {}
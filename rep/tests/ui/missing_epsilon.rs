@@ -0,0 +1,9 @@
+use rep::*;
+
+#[derive(CheckRep)]
+struct Gain {
+    #[rep(approx_eq = 1.0)]
+    value: f64,
+}
+
+fn main() {}
@@ -1,18 +1,857 @@
 extern crate proc_macro;
 
-use proc_macro2::Span;
-use quote::quote;
+use proc_macro2::{Delimiter, Group, Span, TokenStream, TokenTree};
+use quote::{format_ident, quote};
 use quote::ToTokens;
 use syn::spanned::Spanned;
 use syn::{
-	ItemImpl, ImplItem, Stmt, Meta, FnArg, Error,
-    parse_macro_input, Data, DeriveInput, Fields, NestedMeta, Visibility,
-    ImplItemMethod, Lit
+	ItemImpl, ImplItem, ReturnType, Meta, FnArg, Error, Ident, Index, Member, Path, LitStr,
+    parse_macro_input, parse_quote, Data, DeriveInput, Fields, NestedMeta, Visibility,
+    ImplItemMethod, Lit, Pat, Type, Attribute, AttributeArgs, PathArguments, GenericArgument, Generics, WhereClause, WherePredicate, Expr,
+    ExprRange, RangeLimits, UnOp
 };
 
+/// A representation invariant on a single field, parsed from `#[rep]`
+enum Rule {
+    Check,
+    AssertDefault,
+    AssertTrue,
+    AssertFalse,
+    AssertEq(Lit),
+    AssertNe(Lit),
+    AssertGt(Lit),
+    AssertLt(Lit),
+    AssertGe(Lit),
+    AssertLe(Lit),
+    AssertWith(LitStr, Path),
+    Range(LitStr, ExprRange),
+    Finite,
+    NotNan,
+    // the tolerance, which defaults to 1e-6
+    Normalized(Option<Lit>),
+    // the expected value and the tolerance, which must be given
+    ApproxEq(Lit, Option<Lit>),
+    NonEmpty,
+    Len(Lit),
+    MinLen(Lit),
+    MaxLen(Lit),
+    // rules applied to each element, each key or each value of a collection
+    Each(Vec<Rule>),
+    Keys(Vec<Rule>),
+    Values(Vec<Rule>),
+}
+
+/// A field of a structure or enum variant that has representation invariants
+struct RepField {
+    name: String,
+    member: Member,
+    binding: Ident,
+    ty: Type,
+    rules: Vec<Rule>,
+}
+
+/// Parses a single item of a `#[rep]` attribute into a rule
+fn parse_rule(nested: &NestedMeta, rules: &mut Vec<Rule>, use_custom: &mut bool, errors: &mut Vec<TokenStream>) {
+    // #[rep] comes in 2 varieties
+    // 1. literals like #[rep(eq ="my_func")]
+    // 2. paths like #[rep(always_true)]
+    if let NestedMeta::Meta(nested_meta) = nested {
+        match nested_meta {
+            Meta::Path(p) => {
+                if p.is_ident("check") {
+                    rules.push(Rule::Check);
+                } else if p.is_ident("use_custom") {
+                    *use_custom = true;
+                } else if p.is_ident("assert_default") {
+                    rules.push(Rule::AssertDefault);
+                } else if p.is_ident("assert_true") {
+                    rules.push(Rule::AssertTrue);
+                } else if p.is_ident("assert_false") {
+                    rules.push(Rule::AssertFalse);
+                } else if p.is_ident("finite") {
+                    rules.push(Rule::Finite);
+                } else if p.is_ident("not_nan") {
+                    rules.push(Rule::NotNan);
+                } else if p.is_ident("normalized") {
+                    rules.push(Rule::Normalized(None));
+                } else if p.is_ident("non_empty") {
+                    rules.push(Rule::NonEmpty);
+                } else {
+                    errors.push(Error::new(p.span(), "unsupported representation invariant").to_compile_error());
+                }
+            }
+            Meta::NameValue(v) => {
+                let val = v.lit.clone();
+                if v.path.is_ident("assert_eq") {
+                    rules.push(Rule::AssertEq(val));
+                } else if v.path.is_ident("assert_ne") {
+                    rules.push(Rule::AssertNe(val));
+                } else if v.path.is_ident("assert_gt") {
+                    rules.push(Rule::AssertGt(val));
+                } else if v.path.is_ident("assert_lt") {
+                    rules.push(Rule::AssertLt(val));
+                } else if v.path.is_ident("assert_ge") {
+                    rules.push(Rule::AssertGe(val));
+                } else if v.path.is_ident("assert_le") {
+                    rules.push(Rule::AssertLe(val));
+                } else if v.path.is_ident("len") {
+                    rules.push(Rule::Len(val));
+                } else if v.path.is_ident("min_len") {
+                    rules.push(Rule::MinLen(val));
+                } else if v.path.is_ident("max_len") {
+                    rules.push(Rule::MaxLen(val));
+                } else if v.path.is_ident("approx_eq") {
+                    rules.push(Rule::ApproxEq(val, None));
+                } else if v.path.is_ident("epsilon") {
+                    // the tolerance applies to the rule right before it
+                    match rules.last_mut() {
+                        Some(Rule::ApproxEq(_, epsilon @ None)) | Some(Rule::Normalized(epsilon @ None)) => *epsilon = Some(val),
+                        _ => errors.push(Error::new(v.span(), "epsilon must follow approx_eq or normalized").to_compile_error()),
+                    }
+                } else if v.path.is_ident("range") {
+                    match &val {
+                        Lit::Str(source) => match source.parse::<Expr>() {
+                            Ok(Expr::Range(range)) => match check_range(&range) {
+                                Ok(()) => rules.push(Rule::Range(source.clone(), range)),
+                                Err(message) => errors.push(Error::new(val.span(), message).to_compile_error()),
+                            },
+                            _ => errors.push(Error::new(val.span(), "range can only be used with a range such as \"0..10\" or \"-90.0..=90.0\"").to_compile_error()),
+                        },
+                        _ => errors.push(Error::new(val.span(), "range can only be used with a range such as \"0..10\" or \"-90.0..=90.0\"").to_compile_error()),
+                    }
+                } else if v.path.is_ident("assert_with") {
+                    match &val {
+                        Lit::Str(fn_name) => match fn_name.parse::<Path>() {
+                            Ok(fn_to_call) => rules.push(Rule::AssertWith(fn_name.clone(), fn_to_call)),
+                            Err(_) => errors.push(Error::new(val.span(), "assert_with can only be used with the name of a function to call").to_compile_error()),
+                        },
+                        _ => errors.push(Error::new(val.span(), "assert_with can only be used with the name of a function to call").to_compile_error()),
+                    }
+                } else {
+                    errors.push(Error::new(v.span(), "unsupported representation invariant").to_compile_error());
+                }
+            }
+            Meta::List(list) if list.path.is_ident("each") || list.path.is_ident("keys") || list.path.is_ident("values") => {
+                let mut inner = vec![];
+                if list.nested.is_empty() {
+                    errors.push(Error::new(list.span(), "expected at least 1 item").to_compile_error());
+                }
+                for nested in &list.nested {
+                    parse_rule(nested, &mut inner, use_custom, errors);
+                }
+                rules.push(if list.path.is_ident("each") {
+                    Rule::Each(inner)
+                } else if list.path.is_ident("keys") {
+                    Rule::Keys(inner)
+                } else {
+                    Rule::Values(inner)
+                });
+            }
+            _ => {
+                errors.push(Error::new(nested_meta.span(), "unsupported representation invariant").to_compile_error());
+            }
+        }
+    } else {
+        errors.push(Error::new(nested.span(), "invalid usage of #[rep]").to_compile_error());
+    }
+}
+
+/// Returns true if any of the rules, or the rules they apply to elements, is `Rule::Check`
+fn has_check(rules: &[Rule]) -> bool {
+    rules.iter().any(|rule| match rule {
+        Rule::Check => true,
+        Rule::Each(inner) | Rule::Keys(inner) | Rule::Values(inner) => has_check(inner),
+        _ => false,
+    })
+}
+
+/// Returns true if any of the rules applies to elements of a collection
+fn has_element_rules(rules: &[Rule]) -> bool {
+    rules.iter().any(|rule| matches!(rule, Rule::Each(_) | Rule::Keys(_) | Rule::Values(_)))
+}
+
+/// The value of a literal bound of a range
+#[derive(Clone, Copy)]
+enum Bound {
+    Int(i128),
+    Float(f64),
+    Char(char),
+}
+
+/// Returns the value of a range bound if it is a literal, possibly negated
+fn literal_bound(expr: &Expr) -> Option<Bound> {
+    match expr {
+        Expr::Lit(expr_lit) => match &expr_lit.lit {
+            Lit::Int(int) => int.base10_parse().ok().map(Bound::Int),
+            Lit::Float(float) => float.base10_parse().ok().map(Bound::Float),
+            Lit::Char(c) => Some(Bound::Char(c.value())),
+            _ => None,
+        },
+        Expr::Unary(unary) if matches!(unary.op, UnOp::Neg(_)) => match literal_bound(&unary.expr)? {
+            Bound::Int(int) => Some(Bound::Int(-int)),
+            Bound::Float(float) => Some(Bound::Float(-float)),
+            Bound::Char(_) => None,
+        },
+        Expr::Paren(paren) => literal_bound(&paren.expr),
+        _ => None,
+    }
+}
+
+/// Rejects ranges without bounds, closed ranges without an end and ranges with literal bounds that
+/// no value can be in
+fn check_range(range: &ExprRange) -> Result<(), &'static str> {
+    let (from, to) = match (&range.from, &range.to) {
+        (None, None) => return Err("range must have at least one bound"),
+        (_, None) if matches!(range.limits, RangeLimits::Closed(_)) => return Err("range with ..= must have an end"),
+        (Some(from), Some(to)) => (from, to),
+        _ => return Ok(()),
+    };
+
+    let ordering = match (literal_bound(from), literal_bound(to)) {
+        (Some(Bound::Int(from)), Some(Bound::Int(to))) => from.partial_cmp(&to),
+        (Some(Bound::Char(from)), Some(Bound::Char(to))) => from.partial_cmp(&to),
+        (Some(Bound::Int(from)), Some(Bound::Float(to))) => (from as f64).partial_cmp(&to),
+        (Some(Bound::Float(from)), Some(Bound::Int(to))) => from.partial_cmp(&(to as f64)),
+        (Some(Bound::Float(from)), Some(Bound::Float(to))) => from.partial_cmp(&to),
+        _ => return Ok(()),
+    };
+
+    match (ordering, &range.limits) {
+        (Some(std::cmp::Ordering::Greater), _) => Err("range is inverted, its start is greater than its end"),
+        (Some(std::cmp::Ordering::Equal), RangeLimits::HalfOpen(_)) => Err("range is empty, use ..= to include its end"),
+        _ => Ok(()),
+    }
+}
+
+/// Reports rules that are missing an option they require
+fn check_rules(rules: &[Rule], errors: &mut Vec<TokenStream>) {
+    for rule in rules {
+        match rule {
+            Rule::ApproxEq(val, None) => {
+                errors.push(Error::new(val.span(), "approx_eq must be followed by an epsilon, as in approx_eq = 1.0, epsilon = 1e-6").to_compile_error());
+            }
+            Rule::Each(inner) | Rule::Keys(inner) | Rule::Values(inner) => check_rules(inner, errors),
+            _ => {}
+        }
+    }
+}
+
+/// Parses the `#[rep]` attributes of a field or parameter into rules
+///
+/// An attribute may hold several items, as in `#[rep(finite, assert_gt = 0.0)]`.
+fn parse_rules(attrs: &[Attribute], use_custom: &mut bool, errors: &mut Vec<TokenStream>) -> Vec<Rule> {
+    let mut rules = vec![];
+
+    for attr in attrs {
+        if let Ok(Meta::List(meta_list)) = attr.parse_meta() {
+            if meta_list.path.is_ident("rep") {
+                if meta_list.nested.is_empty() {
+                    errors.push(Error::new(meta_list.span(), "expected at least 1 item").to_compile_error());
+                }
+                for nested in &meta_list.nested {
+                    parse_rule(nested, &mut rules, use_custom, errors);
+                }
+            }
+        }
+    }
+    check_rules(&rules, errors);
+
+    rules
+}
+
+/// Collects the fields that have representation invariants
+///
+/// Each field is bound to a variable named `binding` in the generated code.
+fn parse_fields(fields: &Fields, use_custom: &mut bool, errors: &mut Vec<TokenStream>) -> Vec<RepField> {
+    let mut rep_fields = vec![];
+
+    match fields {
+        Fields::Named(fields_named) => {
+            for field in &fields_named.named {
+                let rules = parse_rules(&field.attrs, use_custom, errors);
+                if !rules.is_empty() {
+                    let ident = field.ident.clone().unwrap();
+                    rep_fields.push(RepField {
+                        name: ident.to_string(),
+                        member: Member::Named(ident.clone()),
+                        binding: format_ident!("__rep_{}", ident),
+                        ty: field.ty.clone(),
+                        rules,
+                    });
+                }
+            }
+        }
+        Fields::Unnamed(fields_unnamed) => {
+            for (i, field) in fields_unnamed.unnamed.iter().enumerate() {
+                let rules = parse_rules(&field.attrs, use_custom, errors);
+                if !rules.is_empty() {
+                    rep_fields.push(RepField {
+                        name: i.to_string(),
+                        member: Member::Unnamed(Index::from(i)),
+                        binding: format_ident!("__rep_{}", i),
+                        ty: field.ty.clone(),
+                        rules,
+                    });
+                }
+            }
+        }
+        Fields::Unit => {}
+    }
+
+    rep_fields
+}
+
+/// An invariant over a whole structure or enum, optionally with a message to report
+struct Invariant {
+    expr: Expr,
+    source: String,
+    message: Option<LitStr>,
+}
+
+/// Settings that apply to a whole structure or enum, parsed from `#[rep]` on the type itself
+struct Container {
+    bound: Option<Vec<WherePredicate>>,
+    invariants: Vec<Invariant>,
+    constructor: Option<Span>,
+}
+
+/// Parses the `#[rep]` attributes of a structure or enum
+fn parse_container(attrs: &[Attribute], errors: &mut Vec<TokenStream>) -> Container {
+    let mut container = Container {
+        bound: None,
+        invariants: vec![],
+        constructor: None,
+    };
+
+    for attr in attrs {
+        if let Ok(Meta::List(meta_list)) = attr.parse_meta() {
+            if meta_list.path.is_ident("rep") {
+                // a message belongs to the invariant given before it in the same #[rep]
+                let mut last_invariant = None;
+
+                for nested in &meta_list.nested {
+                    match nested {
+                        NestedMeta::Meta(Meta::NameValue(v)) if v.path.is_ident("invariant") => {
+                            if let Lit::Str(invariant) = &v.lit {
+                                match invariant.parse::<Expr>() {
+                                    Ok(expr) => {
+                                        last_invariant = Some(container.invariants.len());
+                                        container.invariants.push(Invariant {
+                                            expr,
+                                            source: invariant.value(),
+                                            message: None,
+                                        });
+                                    }
+                                    // errors such as an unexpected end of input are reported at the string
+                                    Err(error) => errors.push(Error::new(invariant.span(), error).to_compile_error()),
+                                }
+                            } else {
+                                errors.push(Error::new(v.lit.span(), "invariant must be a string containing an expression").to_compile_error());
+                            }
+                        }
+                        NestedMeta::Meta(Meta::Path(p)) if p.is_ident("constructor") => {
+                            container.constructor = Some(p.span());
+                        }
+                        NestedMeta::Meta(Meta::NameValue(v)) if v.path.is_ident("message") => {
+                            match (&v.lit, last_invariant) {
+                                (Lit::Str(message), Some(i)) => container.invariants[i].message = Some(message.clone()),
+                                (Lit::Str(_), None) => errors.push(Error::new(v.span(), "message must follow an invariant").to_compile_error()),
+                                _ => errors.push(Error::new(v.lit.span(), "message must be a string").to_compile_error()),
+                            }
+                        }
+                        NestedMeta::Meta(Meta::NameValue(v)) if v.path.is_ident("bound") => {
+                            if let Lit::Str(bound) = &v.lit {
+                                // an empty bound removes all inferred bounds
+                                match syn::parse_str::<WhereClause>(&format!("where {}", bound.value())) {
+                                    Ok(where_clause) => container.bound = Some(where_clause.predicates.into_iter().collect()),
+                                    Err(_) => errors.push(Error::new(bound.span(), "bound must be a list of where predicates").to_compile_error()),
+                                }
+                            } else {
+                                errors.push(Error::new(v.lit.span(), "bound must be a string").to_compile_error());
+                            }
+                        }
+                        _ => {
+                            errors.push(Error::new(nested.span(), "unsupported representation option").to_compile_error());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    container
+}
+
+/// Returns true if the given tokens mention the given identifier
+fn mentions(tokens: TokenStream, ident: &str) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(i) => i == ident,
+        TokenTree::Group(group) => mentions(group.stream(), ident),
+        _ => false,
+    })
+}
+
+/// Returns true if the given tokens use the given identifier as a variable, rather than as a
+/// field, a method or part of a path
+fn mentions_variable(tokens: TokenStream, ident: &str) -> bool {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+
+    let punct = |i: Option<usize>| match i.and_then(|i| tokens.get(i)) {
+        Some(TokenTree::Punct(punct)) => Some(punct.as_char()),
+        _ => None,
+    };
+
+    tokens.iter().enumerate().any(|(i, token)| match token {
+        TokenTree::Ident(name) => {
+            // `self.ret` and `Self::ret` are qualified, but not `0..ret` or `Foo { x: ret }`
+            let qualified = match (punct(i.checked_sub(2)), punct(i.checked_sub(1))) {
+                (Some('.'), Some('.')) => false,
+                (_, Some('.')) => true,
+                (Some(':'), Some(':')) => true,
+                _ => false,
+            };
+            name == ident && !qualified
+        }
+        TokenTree::Group(group) => mentions_variable(group.stream(), ident),
+        _ => false,
+    })
+}
+
+/// Adds a `CheckRep` bound for every type parameter used by a field that is recursively checked
+fn add_check_rep_bounds(generics: &mut Generics, fields: &[RepField]) {
+    let checked_types: Vec<&Type> = fields
+        .iter()
+        .filter(|field| has_check(&field.rules))
+        .map(|field| &field.ty)
+        .collect();
+    let type_params: Vec<Ident> = generics.type_params().map(|param| param.ident.clone()).collect();
+
+    for type_param in type_params {
+        if checked_types.iter().any(|ty| mentions(ty.to_token_stream(), &type_param.to_string())) {
+            generics.make_where_clause().predicates.push(parse_quote! { #type_param: rep::CheckRep });
+        }
+    }
+}
+
+/// The code generated to check a rule and to describe its violation
+struct RuleCheck {
+    check: TokenStream,
+    rule: &'static str,
+    expected: TokenStream,
+    actual: TokenStream,
+    message: TokenStream,
+}
+
+/// Generates a check and a description of its violation for a rule applied to the given value
+///
+/// Returns `None` for `Rule::Check` which is handled by recursing on the value and for rules that
+/// apply to elements, see `value_checks`. The type may be `_` if it isn't known.
+fn rule_check(rule: &Rule, value: &TokenStream, ty: &Type, name: &str) -> Option<RuleCheck> {
+    let actual = quote! { Some(format!("{}", #value)) };
+    let rule_check = match rule {
+        Rule::Check | Rule::Each(_) | Rule::Keys(_) | Rule::Values(_) => return None,
+        Rule::AssertDefault => RuleCheck {
+            check: match ty {
+                Type::Infer(_) => quote! {
+                    {
+                        fn default_of<T: Default>(_: &T) -> T {
+                            T::default()
+                        }
+                        #value == default_of(&#value)
+                    }
+                },
+                _ => quote! {
+                    {
+                        let default: #ty = Default::default();
+                        #value == default
+                    }
+                },
+            },
+            rule: "assert_default",
+            expected: quote! { Some(String::from("default")) },
+            actual,
+            message: quote! { format!("{} must be default, not {}", #name, #value) },
+        },
+        Rule::AssertTrue => RuleCheck {
+            check: quote! { #value },
+            rule: "assert_true",
+            expected: quote! { Some(String::from("true")) },
+            actual: quote! { Some(String::from("false")) },
+            message: quote! { format!("{} must be true", #name) },
+        },
+        Rule::AssertFalse => RuleCheck {
+            check: quote! { !#value },
+            rule: "assert_false",
+            expected: quote! { Some(String::from("false")) },
+            actual: quote! { Some(String::from("true")) },
+            message: quote! { format!("{} must be false", #name) },
+        },
+        Rule::AssertEq(val) => RuleCheck {
+            check: quote! { #value == #val },
+            rule: "assert_eq",
+            expected: quote! { Some(format!("== {}", #val)) },
+            actual,
+            message: quote! { format!("{} must be {}, not {}", #name, #val, #value) },
+        },
+        Rule::AssertNe(val) => RuleCheck {
+            check: quote! { #value != #val },
+            rule: "assert_ne",
+            expected: quote! { Some(format!("!= {}", #val)) },
+            actual: quote! { Some(format!("{}", #val)) },
+            message: quote! { format!("{} must not be {}", #name, #val) },
+        },
+        Rule::AssertGt(val) => RuleCheck {
+            check: quote! { #value > #val },
+            rule: "assert_gt",
+            expected: quote! { Some(format!("> {}", #val)) },
+            actual,
+            message: quote! { format!("{} must be > {}, not {}", #name, #val, #value) },
+        },
+        Rule::AssertLt(val) => RuleCheck {
+            check: quote! { #value < #val },
+            rule: "assert_lt",
+            expected: quote! { Some(format!("< {}", #val)) },
+            actual,
+            message: quote! { format!("{} must be < {}, not {}", #name, #val, #value) },
+        },
+        Rule::AssertGe(val) => RuleCheck {
+            check: quote! { #value >= #val },
+            rule: "assert_ge",
+            expected: quote! { Some(format!(">= {}", #val)) },
+            actual,
+            message: quote! { format!("{} must be >= {}, not {}", #name, #val, #value) },
+        },
+        Rule::AssertLe(val) => RuleCheck {
+            check: quote! { #value <= #val },
+            rule: "assert_le",
+            expected: quote! { Some(format!("<= {}", #val)) },
+            actual,
+            message: quote! { format!("{} must be <= {}, not {}", #name, #val, #value) },
+        },
+        Rule::Range(source, range) => {
+            let source = source.value().trim().to_string();
+            let from = range.from.as_ref().map(|from| quote! { #value >= #from });
+            let to = range.to.as_ref().map(|to| match range.limits {
+                RangeLimits::HalfOpen(_) => quote! { #value < #to },
+                RangeLimits::Closed(_) => quote! { #value <= #to },
+            });
+            let conditions: Vec<TokenStream> = from.into_iter().chain(to).collect();
+
+            RuleCheck {
+                check: quote! { #( #conditions )&&* },
+                rule: "range",
+                expected: quote! { Some(String::from(concat!("in ", #source))) },
+                // ranges work for any PartialOrd type, which isn't necessarily Display
+                actual: quote! { Some(format!("{:?}", #value)) },
+                message: quote! { format!("{} must be in {}, not {:?}", #name, #source, #value) },
+            }
+        }
+        Rule::Finite => RuleCheck {
+            check: quote! { #value.is_finite() },
+            rule: "finite",
+            expected: quote! { Some(String::from("finite")) },
+            actual,
+            message: quote! { format!("{} must be finite, not {}", #name, #value) },
+        },
+        Rule::NotNan => RuleCheck {
+            check: quote! { !#value.is_nan() },
+            rule: "not_nan",
+            expected: quote! { Some(String::from("not NaN")) },
+            actual,
+            message: quote! { format!("{} must not be NaN", #name) },
+        },
+        Rule::Normalized(epsilon) => {
+            let epsilon = match epsilon {
+                Some(epsilon) => quote! { #epsilon },
+                None => quote! { 1e-6 },
+            };
+            let norm = quote! { #value.iter().map(|x| (*x as f64) * (*x as f64)).sum::<f64>().sqrt() };
+
+            RuleCheck {
+                check: quote! { (#norm - 1.0).abs() <= #epsilon },
+                rule: "normalized",
+                expected: quote! { Some(format!("norm of 1 ± {}", #epsilon)) },
+                actual: quote! { Some(format!("norm of {}", #norm)) },
+                message: quote! { format!("{} must have a norm of 1 ± {}, not {}", #name, #epsilon, #norm) },
+            }
+        }
+        Rule::ApproxEq(val, epsilon) => {
+            // a missing epsilon has already been reported by check_rules
+            let epsilon = match epsilon {
+                Some(epsilon) => quote! { #epsilon },
+                None => quote! { 0.0 },
+            };
+
+            RuleCheck {
+                check: quote! { (#value - #val).abs() <= #epsilon },
+                rule: "approx_eq",
+                expected: quote! { Some(format!("{} ± {}", #val, #epsilon)) },
+                actual,
+                message: quote! { format!("{} must be {} ± {}, not {}", #name, #val, #epsilon, #value) },
+            }
+        }
+        Rule::NonEmpty => RuleCheck {
+            check: quote! { !#value.is_empty() },
+            rule: "non_empty",
+            expected: quote! { Some(String::from("non-empty")) },
+            actual: quote! { Some(String::from("empty")) },
+            message: quote! { format!("{} must not be empty", #name) },
+        },
+        Rule::Len(val) => RuleCheck {
+            check: quote! { #value.len() == #val },
+            rule: "len",
+            expected: quote! { Some(format!("len == {}", #val)) },
+            actual: quote! { Some(format!("len {}", #value.len())) },
+            message: quote! { format!("{} must have a len of {}, not {}", #name, #val, #value.len()) },
+        },
+        Rule::MinLen(val) => RuleCheck {
+            check: quote! { #value.len() >= #val },
+            rule: "min_len",
+            expected: quote! { Some(format!("len >= {}", #val)) },
+            actual: quote! { Some(format!("len {}", #value.len())) },
+            message: quote! { format!("{} must have a len >= {}, not {}", #name, #val, #value.len()) },
+        },
+        Rule::MaxLen(val) => RuleCheck {
+            check: quote! { #value.len() <= #val },
+            rule: "max_len",
+            expected: quote! { Some(format!("len <= {}", #val)) },
+            actual: quote! { Some(format!("len {}", #value.len())) },
+            message: quote! { format!("{} must have a len <= {}, not {}", #name, #val, #value.len()) },
+        },
+        Rule::AssertWith(fn_name, fn_to_call) => RuleCheck {
+            check: quote! { #fn_to_call ( #value ) },
+            rule: "assert_with",
+            expected: quote! { Some(format!("{}({}) == true", #fn_name, #name)) },
+            actual,
+            message: quote! { format!("{}({}) must be true when {} == {}", #fn_name, #name, #name, #value) },
+        },
+    };
+
+    Some(rule_check)
+}
+
+/// The variant, if any, that generated violations are reported for
+///
+/// Violations are reported for `Self`, named as `std::any::type_name` names it, like the
+/// violations generated by the attribute macros and the default `CheckRep::violations`.
+struct Context {
+    variant: Option<String>,
+}
+
+impl Context {
+    /// Generates a `rep::Violation` for a rule check of the value at the given path
+    fn violation(&self, path: &str, rule_check: &RuleCheck) -> TokenStream {
+        let variant = self.variant_tokens();
+        let RuleCheck { rule, expected, actual, message, .. } = rule_check;
+
+        quote! {
+            rep::Violation {
+                type_name: String::from(std::any::type_name::<Self>()),
+                variant: #variant,
+                path: String::from(#path),
+                rule: String::from(#rule),
+                expected: #expected,
+                actual: #actual,
+                message: #message,
+            }
+        }
+    }
+
+    fn variant_tokens(&self) -> TokenStream {
+        match &self.variant {
+            Some(variant) => quote! { Some(String::from(#variant)) },
+            None => quote! { None },
+        }
+    }
+}
+
+/// Generates the checks of rules applied to a value that is referred to as `self`
+///
+/// The checks are generated both as a condition for `is_correct` and as statements that add the
+/// violations found to `__rep_errors_<depth>`. Rules that apply to elements of a collection loop
+/// over its elements, checking each at the next depth, and make the violations found relative to
+/// `self[index]` or `self[key]`.
+fn value_checks(rules: &[Rule], value: &TokenStream, context: &Context, depth: usize) -> (TokenStream, TokenStream) {
+    let errors = format_ident!("__rep_errors_{}", depth);
+    let mut conditions = vec![];
+    let mut statements = vec![];
+
+    for rule in rules {
+        if let Some(rule_check) = rule_check(rule, value, &parse_quote! { _ }, "self") {
+            let check = &rule_check.check;
+            let violation = context.violation("self", &rule_check);
+            conditions.push(quote! { { #check } });
+            statements.push(quote! {
+                if !{ #check } {
+                    #errors.push(#violation);
+                }
+            });
+            continue;
+        }
+
+        let element = format_ident!("__rep_element_{}", depth);
+        let key = format_ident!("__rep_key_{}", depth);
+        let inner_errors = format_ident!("__rep_errors_{}", depth + 1);
+        match rule {
+            Rule::Each(inner) | Rule::Keys(inner) | Rule::Values(inner) => {
+                let (inner_condition, inner_statements) = value_checks(inner, &quote! { (*#element) }, context, depth + 1);
+                let (condition, pattern, elements, path) = match rule {
+                    Rule::Each(_) => (
+                        quote! { (&#value).into_iter().all(|#element| #inner_condition) },
+                        quote! { (#key, #element) },
+                        quote! { (&#value).into_iter().enumerate() },
+                        quote! { format!("self[{}]", #key) },
+                    ),
+                    Rule::Keys(_) => (
+                        quote! { (&#value).into_iter().all(|(#element, _)| #inner_condition) },
+                        quote! { (#element, _) },
+                        quote! { (&#value).into_iter() },
+                        quote! { format!("self[{:?}]", #element) },
+                    ),
+                    _ => (
+                        quote! { (&#value).into_iter().all(|(_, #element)| #inner_condition) },
+                        quote! { (#key, #element) },
+                        quote! { (&#value).into_iter() },
+                        quote! { format!("self[{:?}]", #key) },
+                    ),
+                };
+                conditions.push(condition);
+                statements.push(quote! {
+                    for #pattern in #elements {
+                        let mut #inner_errors = rep::Violations::new();
+                        #inner_statements
+                        #inner_errors.within(&#path);
+                        #errors.append(&mut #inner_errors);
+                    }
+                });
+            }
+            _ => {
+                conditions.push(quote! { #value.is_correct() });
+                statements.push(quote! {
+                    if let Err(mut errors) = #value.violations() {
+                        #errors.append(&mut errors);
+                    }
+                });
+            }
+        }
+    }
+
+    let condition = if conditions.is_empty() {
+        quote! { true }
+    } else {
+        quote! { #( #conditions )&&* }
+    };
+    (condition, quote! { #( #statements )* })
+}
+
+/// Generates the bodies of `is_correct` and `violations` for the given fields
+///
+/// The generated code expects the fields to be bound by reference and updates `is_correct` or
+/// `c` and `is_error` respectively. Violations of recursively checked fields are made relative to
+/// `self` but keep the type, and variant if any, they were found in.
+fn fields_checks(fields: &[RepField], context: &Context) -> (TokenStream, TokenStream) {
+    let mut checks = vec![];
+    let mut check_violations = vec![];
+    let mut fields_to_recurse_on = vec![];
+    let mut element_conditions = vec![];
+    let mut element_violations = vec![];
+
+    for field in fields {
+        let binding = &field.binding;
+        let value = quote! { (*#binding) };
+        let name = format!("self.{}", field.name);
+
+        for rule in &field.rules {
+            if let Some(rule_check) = rule_check(rule, &value, &field.ty, &name) {
+                let check = &rule_check.check;
+                checks.push(quote! { { #check } });
+                check_violations.push(context.violation(&name, &rule_check));
+            } else if let Rule::Check = rule {
+                fields_to_recurse_on.push((value.clone(), name.clone()));
+            } else {
+                // violations of rules on elements are found relative to the field
+                let (condition, statements) = value_checks(std::slice::from_ref(rule), &value, context, 0);
+                element_conditions.push(condition);
+                element_violations.push(quote! {
+                    let mut __rep_errors_0 = rep::Violations::new();
+                    #statements
+                    if !__rep_errors_0.is_empty() {
+                        __rep_errors_0.within(#name);
+                        c.append(&mut __rep_errors_0);
+                        is_error = true;
+                    }
+                });
+            }
+        }
+    }
+
+    let recursions = fields_to_recurse_on.iter().map(|(value, name)| quote! {
+        let recursed = #value .violations();
+        if let Err(mut errors) = recursed {
+            errors.within(#name);
+            c.append(&mut errors);
+            is_error = true;
+        }
+    });
+    let fields_to_recurse_on = fields_to_recurse_on.iter().map(|(value, _)| value);
+
+    let is_correct = quote! {
+        #( is_correct = is_correct && #checks ; )*
+        #( is_correct = is_correct && #fields_to_recurse_on .is_correct() ; )*
+        #( is_correct = is_correct && #element_conditions ; )*
+    };
+    let violations = quote! {
+        #( if ! #checks { c.push( #check_violations ); is_error = true; } )*
+        #( #recursions )*
+        #( { #element_violations } )*
+    };
+
+    (is_correct, violations)
+}
+
+/// Generates a `try_new` function that constructs a structure from its fields and checks it
+fn try_new(vis: &Visibility, name: &Ident, fields: &Fields) -> TokenStream {
+    let params: Vec<Ident> = fields.iter().enumerate().map(|(i, field)| match &field.ident {
+        Some(ident) => ident.clone(),
+        None => format_ident!("field{}", i),
+    }).collect();
+    let types = fields.iter().map(|field| &field.ty);
+    let construct = match fields {
+        Fields::Named(_) => quote! { Self { #( #params ),* } },
+        Fields::Unnamed(_) => quote! { Self( #( #params ),* ) },
+        Fields::Unit => quote! { Self },
+    };
+    let doc = format!("Creates a `{}` from its fields if its representation is correct", name);
+
+    quote! {
+        #[doc = #doc]
+        #[allow(clippy::too_many_arguments)]
+        #vis fn try_new( #( #params: #types ),* ) -> Result<Self, rep::Violations> {
+            let value = #construct;
+            rep::CheckRep::violations(&value)?;
+            Ok(value)
+        }
+    }
+}
+
+/// Generates a pattern that binds the given fields by reference
+fn fields_pattern(path: TokenStream, fields: &[RepField]) -> TokenStream {
+    let members = fields.iter().map(|field| &field.member);
+    let bindings = fields.iter().map(|field| &field.binding);
+
+    quote! {
+        #path { #( #members: #bindings, )* .. }
+    }
+}
+
 /// A macro for deriving an implementation of `CheckRep`
 ///
-/// The following usages of `#[rep]` are supported.
+/// `#[rep]` may be applied to named or positional fields of structures and enum variants. Only the
+/// variant that is active is checked and positional fields are referred to as `self.0`, `self.1`
+/// and so on.
+///
+/// The following usages of `#[rep]` are supported, and several of them may be combined in one
+/// attribute as in `#[rep(finite, assert_gt = 0.0)]`.
 /// - `#[rep(assert_default)]`
 /// - `#[rep(assert_true)]`
 /// - `#[rep(assert_false)]`
@@ -22,276 +861,680 @@ use syn::{
 /// - `#[rep(assert_ge = 20)]`
 /// - `#[rep(assert_le = 40)]`
 /// - `#[rep(assert_with = "has_valid_id")]`
+/// - `#[rep(range = "-90.0..=90.0")]`, also with `..`, `a..` or `..=b`, for any `PartialOrd + Debug` type
+/// - `#[rep(finite)]` and `#[rep(not_nan)]` for floats
+/// - `#[rep(approx_eq = 1.0, epsilon = 1e-6)]` for floats
+/// - `#[rep(normalized)]` for arrays, slices or vectors of floats whose norm must be 1, within
+///   `1e-6` or the `epsilon` given after it
+/// - `#[rep(non_empty)]`, `#[rep(len = 4)]`, `#[rep(min_len = 1)]` and `#[rep(max_len = 64)]` for
+///   anything with `is_empty` or `len`, such as strings and collections
 /// - `#[rep(check)]`
+/// - `#[rep(each(...))]`, `#[rep(keys(...))]` and `#[rep(values(...))]` apply any of the above to
+///   each element of a collection, or each key or value of a map, which are reported as
+///   `self.points[3]` or `self.scores["alice"]`. Keys must implement `Debug`.
+///
+/// Invariants that relate several fields can be given on the type itself, with an optional message
+/// to report when they don't hold. Any number of them may be given.
+/// - `#[rep(invariant = "self.lo <= self.hi")]`
+/// - `#[rep(invariant = "self.lo <= self.hi", message = "lo must not exceed hi")]`
+///
+/// Putting `#[rep(constructor)]` on a structure generates a `try_new` function that takes the
+/// fields in order and returns the structure if its representation is correct, or the
+/// `rep::Violations` found otherwise.
+///
+/// Generic types are supported. A `CheckRep` bound is added for each type parameter that is used
+/// by a field with `#[rep(check)]`. The inferred bounds can be replaced by putting
+/// `#[rep(bound = "T: CheckRep + PartialOrd")]` on the type itself.
 #[proc_macro_derive(CheckRep, attributes(rep))]
 pub fn derive_check_rep(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
     let name = input.ident;
     let data = input.data;
+    let mut generics = input.generics;
 
-    let mut checks = vec![];
-    let mut check_errors = vec![];
     let mut errors = vec![];
-    let mut fields_to_recurse_on = vec![];
     let mut use_custom = false;
+    let container = parse_container(&input.attrs, &mut errors);
+    let mut all_fields = vec![];
 
-    if let Data::Struct(data_struct) = data {
-        let fields = data_struct.fields;
-        let mut fields_contents = None;
-    	if let Fields::Named(fields_named) = fields {
-            fields_contents = Some(fields_named.named);
-        // } else if let Fields::Unnamed(fields_unnamed) = fields {
-        //     fields_contents = Some(fields_unnamed.unnamed);
-        } else {
-            errors.push(Error::new(fields.span(), "expected named fields").to_compile_error());
-        }
-
-		for (_, field) in fields_contents.unwrap().iter().enumerate() {
-			for attr in field.attrs.clone() {
-				let maybe_meta = attr.parse_meta();
-
-				if let Ok(meta) = maybe_meta {
-					if let Meta::List(meta_list) = meta {
-						if meta_list.path.is_ident("rep") {
-							if meta_list.nested.len() == 1 {
-								let nested = meta_list.nested.first().unwrap();
-
-                                // #[rep] comes in 2 varieties
-                                // 1. literals like #[rep(eq ="my_func")]
-                                // 2. paths like #[rep(always_true)]
-                                if let NestedMeta::Meta(nested_meta) = nested {
-                                    let field_name = field.ident.clone().unwrap();//_or(Ident::new(&i.to_string(), Span::call_site()));
-                                    let field_name_name = field.ident.clone().unwrap().to_string();
-                                    let field_type = field.ty.clone();
-                                    match nested_meta {
-                                        Meta::Path(p) => {
-                                            if p.is_ident("check") {
-                                                fields_to_recurse_on.push(field_name);
-                                            } else if p.is_ident("use_custom") {
-                                                use_custom = true;
-                                            } else if p.is_ident("assert_default") {
-                                                checks.push(quote! {
-                                                    {
-                                                        let default: #field_type = Default::default();
-                                                        self.#field_name == default
-                                                    }
-                                                });
-                                                check_errors.push(quote! {
-                                                    format!("self.{} must be default, not {}", #field_name_name, self.#field_name)
-                                                });
-                                            } else if p.is_ident("assert_true") {
-                                                checks.push(quote! {
-                                                    {
-                                                        self.#field_name
-                                                    }
-                                                });
-                                                check_errors.push(quote! {
-                                                    format!("self.{} must be true", #field_name_name)
-                                                });
-                                            } else if p.is_ident("assert_false") {
-                                                checks.push(quote! {
-                                                    {
-                                                        !self.#field_name
-                                                    }
-                                                });
-                                                check_errors.push(quote! {
-                                                    format!("self.{} must be false", #field_name_name)
-                                                });
-                                            } else {
-                                                errors.push(Error::new(p.span(), "unsupported representation invariant").to_compile_error());
-                                            }
-                                        }
-                                        Meta::NameValue(v) => {
-                                            if v.path.is_ident("assert_eq") {
-                                                let val = v.lit.clone();
-                                                checks.push(quote! {
-                                                    {
-                                                        self.#field_name == #val
-                                                    }
-                                                });
-                                                check_errors.push(quote! {
-                                                    format!("self.{} must be {}, not {}", #field_name_name, #val, self.#field_name)
-                                                });
-                                            } else if v.path.is_ident("assert_ne") {
-                                                let val = v.lit.clone();
-                                                checks.push(quote! {
-                                                    {
-                                                        self.#field_name != #val
-                                                    }
-                                                });
-                                                check_errors.push(quote! {
-                                                    format!("self.{} must not be {}", #field_name_name, #val)
-                                                });
-                                            } else if v.path.is_ident("assert_gt") {
-                                                let val = v.lit.clone();
-                                                checks.push(quote! {
-                                                    {
-                                                        self.#field_name > #val
-                                                    }
-                                                });
-                                                check_errors.push(quote! {
-                                                    format!("self.{} must be > {}, not {}", #field_name_name, #val, self.#field_name)
-                                                });
-                                            } else if v.path.is_ident("assert_lt") {
-                                                let val = v.lit.clone();
-                                                checks.push(quote! {
-                                                    {
-                                                        self.#field_name < #val
-                                                    }
-                                                });
-                                                check_errors.push(quote! {
-                                                    format!("self.{} must be < {}, not {}", #field_name_name, #val, self.#field_name)
-                                                });
-                                            } else if v.path.is_ident("assert_ge") {
-                                                let val = v.lit.clone();
-                                                checks.push(quote! {
-                                                    {
-                                                        self.#field_name >= #val
-                                                    }
-                                                });
-                                                check_errors.push(quote! {
-                                                    format!("self.{} must be >= {}, not {}", #field_name_name, #val, self.#field_name)
-                                                });
-                                            } else if v.path.is_ident("assert_le") {
-                                                let val = v.lit.clone();
-                                                checks.push(quote! {
-                                                    {
-                                                        self.#field_name <= #val
-                                                    }
-                                                });
-                                                check_errors.push(quote! {
-                                                    format!("self.{} must be <= {}, not {}", #field_name_name, #val, self.#field_name)
-                                                });
-                                            } else if v.path.is_ident("assert_with") {
-                                                let val = v.lit.clone();
-                                                if let Lit::Str(fn_name) = val.clone() {
-                                                    let fn_to_call = fn_name.parse::<syn::Path>();
-                                                    if let Ok(fn_to_call) = fn_to_call.clone() {
-                                                        checks.push(quote! {
-                                                        {
-                                                            #fn_to_call ( self.#field_name )
-                                                        }
-                                                        });
-                                                        check_errors.push(quote! {
-                                                            format!("{}(self.{}) must be true when self.{} == {}", #fn_name, #field_name_name, #field_name_name, self.#field_name)
-                                                        });   
-                                                    } else {
-                                                        errors.push(Error::new(val.span(), "assert_with can only be used with the name of a function to call").to_compile_error());
-                                                    }
-                                                } else {
-                                                    errors.push(Error::new(val.span(), "assert_with can only be used with the name of a function to call").to_compile_error());    
-                                                }
-                                            } else {
-                                                errors.push(Error::new(v.span(), "unsupported representation invariant").to_compile_error());
-                                            }
-                                        }
-                                        _ => {
-                                            errors.push(Error::new(nested_meta.span(), "unsupported representation invariant").to_compile_error());
-                                        }
+    // the checks are generated as statements that run with the fields of self bound by reference
+    let mut is_correct_checks = quote! {};
+    let mut violations_checks = quote! {};
+    let mut constructor = None;
+    let type_context = Context { variant: None };
 
-                                    }
-                                } else {
-                                    errors.push(Error::new(nested.span(), "invalid usage of #[rep]").to_compile_error());
-                                }
-							} else {
-                                errors.push(Error::new(meta_list.span(), "expected just 1 item").to_compile_error());
-							}
-						}
-					}
-				}
-			}
-		}
-    } else {
-    	errors.push(Error::new(name.span(), "expected name of structure").to_compile_error());
+    match data {
+        Data::Struct(data_struct) => {
+            let fields = parse_fields(&data_struct.fields, &mut use_custom, &mut errors);
+            if container.constructor.is_some() {
+                constructor = Some(try_new(&input.vis, &name, &data_struct.fields));
+            }
+
+            if !fields.is_empty() {
+                let pattern = fields_pattern(quote! { #name }, &fields);
+                let (is_correct, violations) = fields_checks(&fields, &type_context);
+                is_correct_checks = quote! {
+                    let #pattern = self;
+                    #is_correct
+                };
+                violations_checks = quote! {
+                    let #pattern = self;
+                    #violations
+                };
+            }
+            all_fields.extend(fields);
+        }
+        Data::Enum(data_enum) => {
+            if let Some(span) = container.constructor {
+                errors.push(Error::new(span, "constructor is only supported on structures").to_compile_error());
+            }
+
+            let mut is_correct_arms = vec![];
+            let mut violations_arms = vec![];
+
+            for variant in &data_enum.variants {
+                let variant_name = &variant.ident;
+                let fields = parse_fields(&variant.fields, &mut use_custom, &mut errors);
+                let context = Context { variant: Some(variant_name.to_string()) };
+
+                let pattern = fields_pattern(quote! { #name::#variant_name }, &fields);
+                let (is_correct, violations) = fields_checks(&fields, &context);
+                is_correct_arms.push(quote! { #pattern => { #is_correct } });
+                violations_arms.push(quote! { #pattern => { #violations } });
+                all_fields.extend(fields);
+            }
+
+            // a reference to an enum without variants is not considered empty when matching
+            let matched = if data_enum.variants.is_empty() {
+                quote! { *self }
+            } else {
+                quote! { self }
+            };
+            is_correct_checks = quote! {
+                match #matched {
+                    #( #is_correct_arms )*
+                }
+            };
+            violations_checks = quote! {
+                match #matched {
+                    #( #violations_arms )*
+                }
+            };
+        }
+        Data::Union(_) => {
+            errors.push(Error::new(name.span(), "expected name of structure or enum").to_compile_error());
+        }
+    }
+
+    match container.bound {
+        Some(bound) => generics.make_where_clause().predicates.extend(bound),
+        None => add_check_rep_bounds(&mut generics, &all_fields),
     }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
-    let expanded = if errors.len() > 0 {
+    let expanded = if !errors.is_empty() {
+        // still implement CheckRep so that the errors above are the only ones reported
         quote! {
-            impl rep::CheckRep for #name {
-                fn is_correct(&self) -> Result<(), Vec<String>> {
-                    Ok(())
+            impl #impl_generics rep::CheckRep for #name #ty_generics #where_clause {
+                fn is_correct(&self) -> bool {
+                    true
                 }
-
-                fn check_rep(&self) {}
             }
             #(#errors)*
         }
     } else {
-        if use_custom {
-            quote! {
-                impl rep::CheckRep for #name {
-                    fn is_correct(&self) -> bool {
-                        let mut is_correct = true;
-                        #( is_correct = is_correct && #checks ; )*
-                        #( is_correct = is_correct && self. #fields_to_recurse_on .is_correct() ; )*
-                        is_correct
+        let invariant_checks: Vec<TokenStream> = container.invariants.iter().map(|invariant| {
+            let expr = &invariant.expr;
+            quote! { { #expr } }
+        }).collect();
+        let invariant_violations = container.invariants.iter().map(|invariant| {
+            let source = &invariant.source;
+            let message = match &invariant.message {
+                Some(message) => quote! { String::from(#message) },
+                None => quote! { format!("{} must be true", #source) },
+            };
+            type_context.violation("self", &RuleCheck {
+                check: quote! {},
+                rule: "invariant",
+                expected: quote! { Some(String::from(#source)) },
+                actual: quote! { None },
+                message,
+            })
+        });
+
+        let constructor_impl = constructor.map(|constructor| quote! {
+            impl #impl_generics #name #ty_generics #where_clause {
+                #constructor
+            }
+        });
+
+        let (custom_is_correct, custom_check) = if use_custom {
+            (
+                quote! { is_correct = is_correct && self.c_is_correct(); },
+                quote! {
+                    let custom_check = self.c_violations();
+                    if let Err(mut errors) = custom_check {
+                        c.append(&mut errors);
+                        is_error = true;
                     }
-                    
-                    fn correctness(&self) -> Result<(), Vec<String>> {
-                        let mut c = vec![];
-                        let mut is_error = false;
-                        #( if ! #checks { c.push( #check_errors ); } )*
-                        if c.len() > 0 {
-                            is_error = true;
-                        }
-                        #( 
-                            let recursed = self. #fields_to_recurse_on .correctness();
-                            if let Err(mut errors) = recursed {
-                                c.append(&mut errors);
-                                is_error = true;
-                            }
-                        )*
-                        let custom_check = self.c_correctness();
-                        if let Err(mut errors) = custom_check {
-                            c.append(&mut errors);
-                            is_error = true;
-                        }
-                        if is_error {
-                            Err(c)
+                },
+            )
+        } else {
+            (quote! {}, quote! {})
+        };
+
+        quote! {
+            impl #impl_generics rep::CheckRep for #name #ty_generics #where_clause {
+                #[allow(unused_mut, unreachable_code)]
+                fn is_correct(&self) -> bool {
+                    let mut is_correct = true;
+                    #is_correct_checks
+                    #( is_correct = is_correct && #invariant_checks ; )*
+                    #custom_is_correct
+                    is_correct
+                }
+
+                fn correctness(&self) -> Result<(), Vec<String>> {
+                    self.violations().map_err(|violations| violations.to_strings())
+                }
+
+                #[allow(unused_mut, unreachable_code)]
+                fn violations(&self) -> Result<(), rep::Violations> {
+                    let mut c = rep::Violations::new();
+                    let mut is_error = false;
+                    #violations_checks
+                    #( if ! #invariant_checks { c.push( #invariant_violations ); is_error = true; } )*
+                    #custom_check
+                    if is_error {
+                        Err(c)
+                    } else {
+                        Ok(())
+                    }
+                }
+            }
+
+            #constructor_impl
+        }
+    };
+
+    // hand the output tokens back to the compiler.
+    proc_macro::TokenStream::from(expanded)
+}
+
+/// Generates a condition for running generated checks
+///
+/// The condition is a constant, so disabled checks are optimized away. With `debug`, checks only
+/// run in builds with debug assertions.
+fn checks_enabled(debug: bool) -> TokenStream {
+    if debug {
+        quote! { (cfg!(debug_assertions) && rep::checks_enabled(true)) }
+    } else {
+        quote! { rep::checks_enabled(cfg!(debug_assertions)) }
+    }
+}
+
+/// Returns true if the given type can't be named in the return type of a closure
+fn has_impl_trait(ty: &Type) -> bool {
+    mentions(ty.to_token_stream(), "impl")
+}
+
+/// Generates the return type of a closure wrapping the body of a method with the given return type
+fn closure_return(output: &ReturnType) -> TokenStream {
+    match output {
+        ReturnType::Default => quote! { -> () },
+        // impl Trait can't be written as the return type of a closure so it is inferred
+        ReturnType::Type(_, ty) if has_impl_trait(ty) => quote! {},
+        ReturnType::Type(_, ty) => quote! { -> #ty },
+    }
+}
+
+/// Returns true if the given tokens of a type borrow for the given lifetime, or for an elided one
+fn borrows(tokens: TokenStream, lifetime: Option<&str>) -> bool {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+
+    tokens.iter().enumerate().any(|(i, token)| match token {
+        TokenTree::Punct(punct) if punct.as_char() == '&' => {
+            !matches!(tokens.get(i + 1), Some(TokenTree::Punct(next)) if next.as_char() == '\'')
+        }
+        TokenTree::Punct(punct) if punct.as_char() == '\'' => match tokens.get(i + 1) {
+            Some(TokenTree::Ident(ident)) => ident == "_" || Some(ident.to_string().as_str()) == lifetime,
+            _ => false,
+        },
+        TokenTree::Group(group) => borrows(group.stream(), lifetime),
+        _ => false,
+    })
+}
+
+/// Returns true if the given method takes `&mut self` and returns a borrow of it
+///
+/// The body of such a method can't be wrapped in a closure because what it returns borrows from
+/// the closure, which keeps `self` borrowed when representation is checked. Lifetimes elided in
+/// paths, as in `Iter<T>`, can't be told apart from types without lifetimes and aren't detected.
+fn returns_borrow_of_self(method: &ImplItemMethod) -> bool {
+    let lifetime = match method.sig.receiver() {
+        Some(FnArg::Receiver(receiver)) if receiver.mutability.is_some() => match &receiver.reference {
+            Some((_, lifetime)) => lifetime.as_ref().map(|lifetime| lifetime.ident.to_string()),
+            None => return false,
+        },
+        _ => return false,
+    };
+
+    match &method.sig.output {
+        ReturnType::Type(_, ty) => borrows(ty.to_token_stream(), lifetime.as_deref()),
+        ReturnType::Default => false,
+    }
+}
+
+/// The ways a method can take `self`
+#[derive(Clone, Copy, PartialEq)]
+enum ReceiverKind {
+    Ref,
+    RefMut,
+    Value,
+}
+
+impl ReceiverKind {
+    fn of(method: &ImplItemMethod) -> Option<Self> {
+        match method.sig.receiver() {
+            Some(FnArg::Receiver(receiver)) => Some(match (&receiver.reference, &receiver.mutability) {
+                (Some(_), Some(_)) => ReceiverKind::RefMut,
+                (Some(_), None) => ReceiverKind::Ref,
+                (None, _) => ReceiverKind::Value,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Options given to `#[check_rep(...)]`
+struct CheckOptions {
+    on_unwind: bool,
+    debug: bool,
+    rollback: bool,
+    include: Vec<String>,
+    receivers: Vec<ReceiverKind>,
+    skip: Vec<String>,
+    // whether any of the options for choosing methods of an impl block were given
+    targeting: Option<Span>,
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        Self {
+            on_unwind: false,
+            debug: false,
+            rollback: false,
+            include: vec![String::from("pub")],
+            receivers: vec![ReceiverKind::RefMut],
+            skip: vec![],
+            targeting: None,
+        }
+    }
+}
+
+impl CheckOptions {
+    /// Returns true if the given method of an impl block should be instrumented
+    fn targets(&self, method: &ImplItemMethod) -> bool {
+        let receiver = ReceiverKind::of(method);
+
+        matches!(receiver, Some(receiver) if self.receivers.contains(&receiver)) && self.includes(method)
+    }
+
+    /// Returns true if the given method of an impl block should be instrumented if it's a constructor
+    ///
+    /// The value constructed by an async method can't be checked, so those are left alone.
+    fn targets_constructor(&self, method: &ImplItemMethod) -> bool {
+        method.sig.receiver().is_none() && method.sig.asyncness.is_none() && self.includes(method)
+    }
+
+    fn includes(&self, method: &ImplItemMethod) -> bool {
+        let visibility = match &method.vis {
+            Visibility::Inherited => String::from("private"),
+            vis => vis.to_token_stream().to_string().replace(' ', ""),
+        };
+
+        self.include.contains(&visibility)
+            && !self.skip.contains(&method.sig.ident.to_string())
+            && !method.attrs.iter().any(|attr| attr.path.is_ident("rep_skip"))
+    }
+}
+
+/// Splits a string of comma-separated items
+fn split_list(list: &LitStr) -> Vec<String> {
+    list.value().split(',').map(|item| item.trim().to_string()).filter(|item| !item.is_empty()).collect()
+}
+
+/// Parses the options given to `#[check_rep(...)]`
+fn parse_check_options(args: &[NestedMeta]) -> Result<CheckOptions, TokenStream> {
+    let mut options = CheckOptions::default();
+
+    for arg in args {
+        match arg {
+            NestedMeta::Meta(Meta::Path(p)) if p.is_ident("on_unwind") => options.on_unwind = true,
+            NestedMeta::Meta(Meta::Path(p)) if p.is_ident("rollback") => options.rollback = true,
+            NestedMeta::Meta(Meta::Path(p)) if p.is_ident("debug") => options.debug = true,
+            NestedMeta::Meta(Meta::NameValue(v)) => {
+                let list = match &v.lit {
+                    Lit::Str(list) => list,
+                    _ => return Err(Error::new(v.lit.span(), "expected a string of comma-separated items").to_compile_error()),
+                };
+                options.targeting = Some(v.span());
+
+                if v.path.is_ident("include") {
+                    let mut include = vec![];
+                    for vis in split_list(list) {
+                        if vis == "private" {
+                            include.push(vis);
                         } else {
-                            Ok(())
+                            match syn::parse_str::<Visibility>(&vis) {
+                                Ok(Visibility::Inherited) | Err(_) => {
+                                    return Err(Error::new(list.span(), format!("expected a visibility such as pub, pub(crate) or private, not {}", vis)).to_compile_error());
+                                }
+                                Ok(vis) => include.push(vis.to_token_stream().to_string().replace(' ', "")),
+                            }
                         }
                     }
+                    options.include = include;
+                } else if v.path.is_ident("receivers") {
+                    let mut receivers = vec![];
+                    for receiver in split_list(list) {
+                        receivers.push(match receiver.split_whitespace().collect::<Vec<_>>().join(" ").as_str() {
+                            "&self" | "& self" => ReceiverKind::Ref,
+                            "&mut self" | "& mut self" => ReceiverKind::RefMut,
+                            "self" | "mut self" => ReceiverKind::Value,
+                            _ => return Err(Error::new(list.span(), format!("expected &self, &mut self or self, not {}", receiver)).to_compile_error()),
+                        });
+                    }
+                    options.receivers = receivers;
+                } else if v.path.is_ident("skip") {
+                    options.skip = split_list(list);
+                } else {
+                    return Err(Error::new(v.path.span(), "unsupported option").to_compile_error());
                 }
             }
+            _ => return Err(Error::new(arg.span(), "unsupported option").to_compile_error()),
+        }
+    }
+
+    Ok(options)
+}
+
+/// Inserts calls to `check_rep` at the start and/or on every exit of a method
+///
+/// The post-check wraps the body in a closure so that it also runs after an early `return` or `?`
+/// and so that the value the body evaluates to is still returned. With `on_unwind`, a panic in the
+/// body is caught so that representation can be checked before the panic continues. With
+/// `rollback`, methods that take `&mut self` put back a clone of `self` made at the start if
+/// representation is incorrect at the end. All checks only run when `checks_enabled` allows it and
+/// the call is sampled by `rep::should_check`.
+fn instrument(mut method: ImplItemMethod, require: bool, ensure: bool, options: &CheckOptions) -> TokenStream {
+    // whether a call is checked is decided once, at the start, so that sampling skips or runs
+    // all checks of the call
+    let enabled = checks_enabled(options.debug);
+    let sampled = match ReceiverKind::of(&method) {
+        Some(ReceiverKind::Value) => quote! { rep::should_check(&self) },
+        _ => quote! { rep::should_check(&*self) },
+    };
+    let decide = quote! { let __rep_check = #enabled && #sampled; };
+    let pre = if require {
+        quote! {
+            if __rep_check {
+                self.check_rep();
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    if ensure {
+        if let Some(asyncness) = &method.sig.asyncness {
+            return Error::new(asyncness.span(), "representation can't be checked after an async method").to_compile_error();
+        }
+        if let Some(FnArg::Receiver(receiver)) = method.sig.inputs.first() {
+            if receiver.reference.is_none() {
+                return Error::new(receiver.span(), "representation can't be checked after a method that takes self by value").to_compile_error();
+            }
+        }
+        if returns_borrow_of_self(&method) {
+            return Error::new(method.sig.output.span(), "representation can't be checked after a method that returns a borrow of self").to_compile_error();
+        }
+
+        let ret = closure_return(&method.sig.output);
+        let rollback = options.rollback && ReceiverKind::of(&method) == Some(ReceiverKind::RefMut);
+        let (snapshot, post) = if rollback {
+            (
+                quote! {
+                    let __rep_snapshot = if __rep_check {
+                        Some(std::clone::Clone::clone(&*self))
+                    } else {
+                        None
+                    };
+                },
+                quote! {
+                    if let Some(snapshot) = __rep_snapshot {
+                        rep::rollback_if_incorrect(self, snapshot);
+                    }
+                },
+            )
         } else {
+            (quote! {}, quote! {
+                if __rep_check {
+                    self.check_rep();
+                }
+            })
+        };
+        let block = &method.block;
+        let body = if options.on_unwind {
             quote! {
-                impl rep::CheckRep for #name {
-                    fn is_correct(&self) -> bool {
-                        let mut is_correct = true;
-                        #( is_correct = is_correct && #checks ; )*
-                        #( is_correct = is_correct && self. #fields_to_recurse_on .is_correct() ; )*
-                        is_correct
+                match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| #ret #block)) {
+                    Ok(ret) => ret,
+                    Err(payload) => {
+                        if __rep_check {
+                            rep::check_rep_on_unwind(self);
+                        }
+                        std::panic::resume_unwind(payload)
                     }
+                }
+            }
+        } else {
+            quote! { (|| #ret #block)() }
+        };
+        method.block = parse_quote! {
+            {
+                #decide
+                #pre
+                #snapshot
+                #[allow(clippy::redundant_closure_call)]
+                let __rep_ret = #body;
+                #post
+                __rep_ret
+            }
+        };
+    } else {
+        method.block.stmts.insert(0, parse_quote! {
+            {
+                #decide
+                #pre
+            }
+        });
+    }
+
+    method.to_token_stream()
+}
 
-                    fn correctness(&self) -> Result<(), Vec<String>> {
-                        let mut c = vec![];
-                        let mut is_error = false;
-                        #( if ! #checks { c.push( #check_errors ); } )*
-                        if c.len() > 0 {
-                            is_error = true;
+/// Inserts checks of the arguments of a method at its start, according to the `#[rep]` attributes
+/// of its parameters, and removes those attributes
+///
+/// Violations are reported for the parameter and handled like any other. Returns errors for
+/// attributes that can't be applied to parameters.
+fn check_arguments(method: &mut ImplItemMethod, debug: bool) -> TokenStream {
+    let enabled = checks_enabled(debug);
+    let method_name = method.sig.ident.to_string();
+    let mut errors = vec![];
+    let mut checks = vec![];
+
+    for input in &mut method.sig.inputs {
+        if let FnArg::Typed(pat_type) = input {
+            let mut use_custom = false;
+            let rules = parse_rules(&pat_type.attrs, &mut use_custom, &mut errors);
+            pat_type.attrs.retain(|attr| !attr.path.is_ident("rep"));
+            if use_custom {
+                errors.push(Error::new(pat_type.span(), "use_custom is only supported on fields").to_compile_error());
+            }
+            if has_element_rules(&rules) {
+                errors.push(Error::new(pat_type.span(), "each, keys and values are only supported on fields").to_compile_error());
+                continue;
+            }
+            if rules.is_empty() {
+                continue;
+            }
+
+            let ident = match &*pat_type.pat {
+                Pat::Ident(pat_ident) => pat_ident.ident.clone(),
+                pat => {
+                    errors.push(Error::new(pat.span(), "only parameters bound to a name can be checked").to_compile_error());
+                    continue;
+                }
+            };
+            let name = ident.to_string();
+            let value = quote! { (#ident) };
+
+            for rule in &rules {
+                checks.push(match rule_check(rule, &value, &pat_type.ty, &name) {
+                    Some(RuleCheck { check, rule, expected, actual, message }) => quote! {
+                        if !{ #check } {
+                            rep::handle_violations(&rep::Violations::from(rep::Violation {
+                                type_name: String::from(std::any::type_name::<Self>()),
+                                variant: None,
+                                path: String::from(#name),
+                                rule: String::from(#rule),
+                                expected: #expected,
+                                actual: #actual,
+                                message: format!("{} in call to {}", #message, #method_name),
+                            }));
                         }
-                        #( 
-                            let recursed = self. #fields_to_recurse_on .correctness();
-                            if let Err(mut errors) = recursed {
-                                c.append(&mut errors);
-                                is_error = true;
+                    },
+                    None => quote! {
+                        if let Err(mut violations) = #ident.violations() {
+                            violations.within(#name);
+                            for violation in violations.iter_mut() {
+                                violation.message = format!("{} in call to {}", violation.message, #method_name);
                             }
-                        )*
-                        if is_error {
-                            Err(c)
-                        } else {
-                            Ok(())
+                            rep::handle_violations(&violations);
                         }
+                    },
+                });
+            }
+        }
+    }
+
+    if !checks.is_empty() {
+        method.block.stmts.insert(0, parse_quote! {
+            if #enabled {
+                #( #checks )*
+            }
+        });
+    }
+
+    quote! { #( #errors )* }
+}
+
+/// How a constructor hands out the value it constructs
+enum Construct {
+    Value,
+    Result,
+    Option,
+}
+
+/// Returns how a method with the given return type hands out a constructed value
+///
+/// The constructed type must be `Self` or `self_ty` if it is given. Otherwise any type that isn't
+/// a `Result` or an `Option` is taken to be the constructed type.
+fn construct_kind(output: &ReturnType, self_ty: Option<&Type>) -> Option<Construct> {
+    let is_self = |ty: &Type| match self_ty {
+        Some(self_ty) => {
+            let ty = ty.to_token_stream().to_string();
+            ty == "Self" || ty == self_ty.to_token_stream().to_string()
+        }
+        None => true,
+    };
+
+    let ty = match output {
+        ReturnType::Type(_, ty) => ty,
+        ReturnType::Default => return None,
+    };
+    if let Type::Path(type_path) = &**ty {
+        if let Some(segment) = type_path.path.segments.last() {
+            if segment.ident == "Result" || segment.ident == "Option" {
+                if let PathArguments::AngleBracketed(args) = &segment.arguments {
+                    if let Some(GenericArgument::Type(inner)) = args.args.first() {
+                        return match (is_self(inner), segment.ident == "Result") {
+                            (true, true) => Some(Construct::Result),
+                            (true, false) => Some(Construct::Option),
+                            (false, _) => None,
+                        };
                     }
                 }
-            }            
+            }
+        }
+    }
+
+    if is_self(ty) {
+        Some(Construct::Value)
+    } else {
+        None
+    }
+}
+
+/// Inserts a call to `check_rep` on the value constructed by a method before it is returned
+///
+/// The call only runs when `checks_enabled` allows it and the value is sampled by
+/// `rep::should_check`.
+fn instrument_constructor(mut method: ImplItemMethod, construct: Construct, debug: bool) -> TokenStream {
+    if let Some(asyncness) = &method.sig.asyncness {
+        return Error::new(asyncness.span(), "representation can't be checked after an async method").to_compile_error();
+    }
+
+    let ret = match &method.sig.output {
+        ReturnType::Type(_, ty) => quote! { -> #ty },
+        ReturnType::Default => quote! {},
+    };
+    let check = match construct {
+        Construct::Value => quote! {
+            if rep::should_check(&__rep_ret) {
+                rep::CheckRep::check_rep(&__rep_ret);
+            }
+        },
+        Construct::Result => quote! {
+            if let Ok(constructed) = &__rep_ret {
+                if rep::should_check(constructed) {
+                    rep::CheckRep::check_rep(constructed);
+                }
+            }
+        },
+        Construct::Option => quote! {
+            if let Some(constructed) = &__rep_ret {
+                if rep::should_check(constructed) {
+                    rep::CheckRep::check_rep(constructed);
+                }
+            }
+        },
+    };
+    let enabled = checks_enabled(debug);
+    let block = &method.block;
+    method.block = parse_quote! {
+        {
+            #[allow(clippy::redundant_closure_call)]
+            let __rep_ret = (|| #ret #block)();
+            if #enabled {
+                #check
+            }
+            __rep_ret
         }
     };
 
-    // hand the output tokens back to the compiler.
-    proc_macro::TokenStream::from(expanded)
+    method.to_token_stream()
 }
 
 /// A macro that auto-inserts calls to `check_rep`
@@ -300,59 +1543,122 @@ pub fn derive_check_rep(input: proc_macro::TokenStream) -> proc_macro::TokenStre
 /// - Visiblity is `pub`
 /// - Parameters include `&mut self`
 ///
+/// Constructors, which are `pub` methods without `self` that return `Self`, `Result<Self, E>` or
+/// `Option<Self>`, are checked too. The constructed value is checked before it is returned.
+///
 /// You may also apply it to a method in an `impl` block regardless of the method's signature.
+///
+/// The call at the end of a method runs however the method returns, including through `return`
+/// and `?`, and the method's return value is left untouched.
+///
+/// The following options are supported.
+/// - `#[check_rep(on_unwind)]` also checks representation when a method panics, before the panic
+///   continues, reporting violations without panicking again
+/// - `#[check_rep(rollback)]` makes methods that take `&mut self` undo their changes if they leave
+///   representation incorrect, before the violations are handled. `Self` must implement `Clone`.
+/// - `#[check_rep(debug)]` only checks representation in builds with debug assertions, like
+///   `debug_assert!`, even if the `always` feature of `rep` is enabled
+/// - `#[check_rep(include = "pub, pub(crate)")]` chooses the visibilities of methods of an impl
+///   block to check, where `private` stands for methods without `pub`
+/// - `#[check_rep(receivers = "&self, &mut self, self")]` chooses how methods of an impl block
+///   must take `self` to be checked. Methods that take `self` by value are only checked at the start.
+/// - `#[check_rep(skip = "reset, clear")]` leaves the named methods of an impl block unchecked
+///
+/// Methods of an impl block can also be left unchecked by marking them with `#[rep_skip]`.
+///
+/// Parameters of methods can be marked with the same `#[rep(...)]` rules as fields, such as
+/// `#[rep(assert_gt = 0)] r: i32`. Arguments are checked before the body runs and violations name
+/// the parameter and the method.
 #[proc_macro_attribute]
-pub fn check_rep(_attr: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    if let Ok(mut impl_block) = syn::parse::<ItemImpl>(item.clone().into()) {
-        let mut new_impl_block = impl_block.clone();
-        new_impl_block.items = vec![];
+pub fn check_rep(attr: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let args = parse_macro_input!(attr as AttributeArgs);
+    let options = match parse_check_options(&args) {
+        Ok(options) => options,
+        Err(error) => return error.into(),
+    };
 
+    if let Ok(mut impl_block) = syn::parse::<ItemImpl>(item.clone()) {
         // loop through all items
-        // see if the item is pub, accepts &mut self
+        // see if the item has one of the included visibilities and receivers and isn't skipped
         // if so, insert calls to check rep
-    	for impl_item in &mut impl_block.items {
-    		let mut new_impl_item = impl_item.clone();
-
-    		if let ImplItem::Method(mut impl_item_method) = impl_item.clone() {
-    			let mut new_impl_item_method = impl_item_method.clone();
+        let mut argument_errors = TokenStream::new();
+        for impl_item in &mut impl_block.items {
+            if let ImplItem::Method(impl_item_method) = impl_item {
+                argument_errors.extend(check_arguments(impl_item_method, options.debug));
+                let targeted = options.targets(impl_item_method);
+                let construct = if options.targets_constructor(impl_item_method) {
+                    construct_kind(&impl_item_method.sig.output, Some(&impl_block.self_ty))
+                } else {
+                    None
+                };
+                impl_item_method.attrs.retain(|attr| !attr.path.is_ident("rep_skip"));
 
-                if let Visibility::Public(_) = new_impl_item_method.vis {
-                    if new_impl_item_method.sig.inputs.iter().fold(false, |_, input| if let FnArg::Receiver(receiver) = input {
-                        receiver.mutability.is_some()
-                    } else {
-                        false
-                    }) {
-                        // insert calls to check rep at start and end of method
-                        impl_item_method.block.stmts.insert(0, syn::parse::<Stmt>(quote! {
-                            self.check_rep();
-                        }.into()).unwrap());
-                        impl_item_method.block.stmts.push(syn::parse::<Stmt>(quote! {
-                            self.check_rep();
-                        }.into()).unwrap());
-
-                        new_impl_item_method.block.stmts = impl_item_method.block.stmts;
-                        new_impl_item = ImplItem::Method(new_impl_item_method); 
-                    }
+                if let Some(construct) = construct {
+                    *impl_item = ImplItem::Verbatim(instrument_constructor(impl_item_method.clone(), construct, options.debug));
+                } else if targeted {
+                    // representation can't be checked after self has been consumed, while it is
+                    // borrowed by the returned value or after an async method
+                    let ensure = ReceiverKind::of(impl_item_method) != Some(ReceiverKind::Value)
+                        && !returns_borrow_of_self(impl_item_method)
+                        && impl_item_method.sig.asyncness.is_none();
+                    *impl_item = ImplItem::Verbatim(instrument(impl_item_method.clone(), true, ensure, &options));
                 }
-    		}
-
-            new_impl_block.items.push(new_impl_item);
-    	}
-
-    	new_impl_block.to_token_stream().into()
-    } else if let Ok(mut impl_item_method) = syn::parse::<ImplItemMethod>(item.clone().into()) {
-        // insert calls to check rep at start and end of method
-        impl_item_method.block.stmts.insert(0, syn::parse::<Stmt>(quote! {
-            self.check_rep();
-        }.into()).unwrap());
-        impl_item_method.block.stmts.push(syn::parse::<Stmt>(quote! {
-            self.check_rep();
-        }.into()).unwrap());
-        impl_item_method.to_token_stream().into()
+            }
+        }
+
+        let impl_block = impl_block.to_token_stream();
+        (quote! {
+            #impl_block
+            #argument_errors
+        }).into()
+    } else if let Ok(mut impl_item_method) = syn::parse::<ImplItemMethod>(item) {
+        if let Some(span) = options.targeting {
+            return Error::new(span, "this option is only supported on impl blocks").to_compile_error().into();
+        }
+        let argument_errors = check_arguments(&mut impl_item_method, options.debug);
+        let method = match construct_kind(&impl_item_method.sig.output, None) {
+            Some(construct) if impl_item_method.sig.receiver().is_none() => instrument_constructor(impl_item_method, construct, options.debug),
+            _ => instrument(impl_item_method, true, true, &options),
+        };
+
+        (quote! {
+            #method
+            #argument_errors
+        }).into()
     } else {
         let error = Error::new(Span::call_site(), "expected impl block or method").to_compile_error();
 
-    	(quote! {
+        (quote! {
+            #error
+        }).into()
+    }
+}
+
+/// A macro that inserts a call to `check_rep` on the value constructed by given method
+///
+/// The method may return the constructed value directly or inside a `Result` or an `Option`, in
+/// which case only an `Ok` or `Some` value is checked. With `debug`, the value is only checked in
+/// builds with debug assertions, as with `#[check_rep(debug)]`.
+#[proc_macro_attribute]
+pub fn construct_rep(attr: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let args = parse_macro_input!(attr as AttributeArgs);
+    let mut debug = false;
+    for arg in &args {
+        match arg {
+            NestedMeta::Meta(Meta::Path(p)) if p.is_ident("debug") => debug = true,
+            _ => return Error::new(arg.span(), "unsupported option").to_compile_error().into(),
+        }
+    }
+
+    if let Ok(impl_item_method) = syn::parse::<ImplItemMethod>(item) {
+        match construct_kind(&impl_item_method.sig.output, None) {
+            Some(construct) => instrument_constructor(impl_item_method, construct, debug).into(),
+            None => Error::new(impl_item_method.sig.output.span(), "expected a method returning a value").to_compile_error().into(),
+        }
+    } else {
+        let error = Error::new(Span::call_site(), "expected method").to_compile_error();
+
+        (quote! {
             #error
         }).into()
     }
@@ -361,12 +1667,8 @@ pub fn check_rep(_attr: proc_macro::TokenStream, item: proc_macro::TokenStream)
 /// A macro that inserts a call to `check_rep` at the start of given method
 #[proc_macro_attribute]
 pub fn require_rep(_attr: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    if let Ok(mut impl_item_method) = syn::parse::<ImplItemMethod>(item.clone().into()) {
-        // insert calls to check rep at start and end of method
-        impl_item_method.block.stmts.insert(0, syn::parse::<Stmt>(quote! {
-            self.check_rep();
-        }.into()).unwrap());
-        impl_item_method.to_token_stream().into()
+    if let Ok(impl_item_method) = syn::parse::<ImplItemMethod>(item) {
+        instrument(impl_item_method, true, false, &CheckOptions::default()).into()
     } else {
         let error = Error::new(Span::call_site(), "expected method").to_compile_error();
 
@@ -376,15 +1678,13 @@ pub fn require_rep(_attr: proc_macro::TokenStream, item: proc_macro::TokenStream
     }
 }
 
-/// A macro that inserts a call to `check_rep` at the start of given method
+/// A macro that inserts a call to `check_rep` at the end of given method
+///
+/// The call runs however the method returns, including through `return` and `?`.
 #[proc_macro_attribute]
 pub fn ensure_rep(_attr: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    if let Ok(mut impl_item_method) = syn::parse::<ImplItemMethod>(item.clone().into()) {
-        // insert calls to check rep at start and end of method
-        impl_item_method.block.stmts.push(syn::parse::<Stmt>(quote! {
-            self.check_rep();
-        }.into()).unwrap());
-        impl_item_method.to_token_stream().into()
+    if let Ok(impl_item_method) = syn::parse::<ImplItemMethod>(item) {
+        instrument(impl_item_method, false, true, &CheckOptions::default()).into()
     } else {
         let error = Error::new(Span::call_site(), "expected method").to_compile_error();
 
@@ -392,4 +1692,259 @@ pub fn ensure_rep(_attr: proc_macro::TokenStream, item: proc_macro::TokenStream)
             #error
         }).into()
     }
-}
\ No newline at end of file
+}
+
+/// Replaces every call to `old(...)` in the given tokens with a variable, adding the expression
+/// that the variable must be initialized with to `captures`
+fn capture_old(tokens: TokenStream, captures: &mut Vec<TokenStream>) -> TokenStream {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    let mut replaced = vec![];
+    let mut i = 0;
+
+    while i < tokens.len() {
+        // old(...) but not x.old(...) or x::old(...)
+        let is_call = match (&tokens[i], tokens.get(i + 1)) {
+            (TokenTree::Ident(ident), Some(TokenTree::Group(group))) => {
+                ident == "old"
+                    && group.delimiter() == Delimiter::Parenthesis
+                    && !matches!(i.checked_sub(1).map(|j| &tokens[j]), Some(TokenTree::Punct(p)) if p.as_char() == '.' || p.as_char() == ':')
+            }
+            _ => false,
+        };
+
+        if is_call {
+            if let TokenTree::Group(group) = &tokens[i + 1] {
+                let old = format_ident!("__rep_old_{}", captures.len());
+                captures.push(group.stream());
+                replaced.push(TokenTree::Ident(old));
+            }
+            i += 2;
+        } else {
+            replaced.push(match &tokens[i] {
+                TokenTree::Group(group) => {
+                    let mut new_group = Group::new(group.delimiter(), capture_old(group.stream(), captures));
+                    new_group.set_span(group.span());
+                    TokenTree::Group(new_group)
+                }
+                token => token.clone(),
+            });
+            i += 1;
+        }
+    }
+
+    replaced.into_iter().collect()
+}
+
+/// Generates a check of a method contract that hands a violation to `rep::handle_violations`
+///
+/// The check only runs when `checks_enabled` allows it.
+fn contract_check(condition: &TokenStream, source: &str, rule: &str, message: &str) -> TokenStream {
+    let enabled = checks_enabled(false);
+
+    quote! {
+        if #enabled && !(#condition) {
+            rep::handle_violations(&rep::Violations::from(rep::Violation {
+                type_name: String::from(std::any::type_name::<Self>()),
+                variant: None,
+                path: String::from("self"),
+                rule: String::from(#rule),
+                expected: Some(String::from(#source)),
+                actual: None,
+                message: String::from(#message),
+            }));
+        }
+    }
+}
+
+/// A macro that checks a precondition at the start of given method
+///
+/// The precondition is an expression in a string, such as `#[requires("idx < self.len()")]`, that
+/// may use `self` and the method's parameters. If it doesn't hold, a violation with the rule
+/// `requires` is handled like any other.
+#[proc_macro_attribute]
+pub fn requires(attr: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let source = parse_macro_input!(attr as LitStr);
+    let mut method = parse_macro_input!(item as ImplItemMethod);
+    let condition = match source.parse::<Expr>() {
+        Ok(condition) => condition.to_token_stream(),
+        Err(error) => return error.to_compile_error().into(),
+    };
+
+    let message = format!("{} must be true on entry to {}", source.value(), method.sig.ident);
+    let check = contract_check(&condition, &source.value(), "requires", &message);
+    method.block.stmts.insert(0, parse_quote! { #check });
+
+    method.to_token_stream().into()
+}
+
+/// A macro that checks a postcondition at the end of given method
+///
+/// The postcondition is an expression in a string, such as
+/// `#[ensures("self.len() == old(self.len()) + 1")]`, where `old(expr)` stands for the value `expr`
+/// had on entry to the method. Such values are cloned before the method runs. The returned value
+/// can be referred to as `ret`, which is a reference to it, so no parameter may be named `ret`. The
+/// check runs
+/// however the method returns, including through `return` and `?`. If the postcondition doesn't
+/// hold, a violation with the rule `ensures` is handled like any other.
+#[proc_macro_attribute]
+pub fn ensures(attr: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let source = parse_macro_input!(attr as LitStr);
+    let mut method = parse_macro_input!(item as ImplItemMethod);
+    let condition = match source.parse::<Expr>() {
+        Ok(condition) => condition.to_token_stream(),
+        Err(error) => return error.to_compile_error().into(),
+    };
+    if let Some(asyncness) = &method.sig.asyncness {
+        return Error::new(asyncness.span(), "postconditions can't be checked after an async method").to_compile_error().into();
+    }
+    if returns_borrow_of_self(&method) {
+        return Error::new(method.sig.output.span(), "postconditions can't be checked after a method that returns a borrow of self").to_compile_error().into();
+    }
+    // ret stands for the returned value in postconditions
+    for input in &method.sig.inputs {
+        if let FnArg::Typed(pat_type) = input {
+            if matches!(&*pat_type.pat, Pat::Ident(pat) if pat.ident == "ret") {
+                return Error::new(pat_type.pat.span(), "a parameter can't be named ret in a method with postconditions, as ret refers to the returned value").to_compile_error().into();
+            }
+        }
+    }
+
+    let mut captures = vec![];
+    let condition = capture_old(condition, &mut captures);
+    let olds: Vec<Ident> = (0..captures.len()).map(|i| format_ident!("__rep_old_{}", i)).collect();
+    let message = format!("{} must be true on exit from {}", source.value(), method.sig.ident);
+    let check = contract_check(&condition, &source.value(), "ensures", &message);
+    // the returned value is only bound when it is used so that it isn't reported as unused
+    let check = if mentions_variable(condition, "ret") {
+        quote! {
+            {
+                let ret = &__rep_ret;
+                #check
+            }
+        }
+    } else {
+        check
+    };
+    // values from entry are only cloned when checks are enabled
+    let enabled = checks_enabled(false);
+    let (entry, check) = if olds.is_empty() {
+        (quote! {}, check)
+    } else {
+        (
+            quote! {
+                #(
+                    let #olds = if #enabled {
+                        Some(std::clone::Clone::clone(&(#captures)))
+                    } else {
+                        None
+                    };
+                )*
+            },
+            quote! {
+                if let ( #( Some(#olds), )* ) = ( #( #olds, )* ) {
+                    #check
+                }
+            },
+        )
+    };
+    check_on_exit(&mut method, entry, check);
+
+    method.to_token_stream().into()
+}
+
+/// A macro that checks the value returned by given method
+///
+/// The value is checked with the same rules as fields, such as
+/// `#[ensures_ret(assert_ge = 0.0, assert_le = 1.0)]`. The check runs however the method returns,
+/// including through `return` and `?`. Violations are reported for `ret` and handled like any
+/// other.
+#[proc_macro_attribute]
+pub fn ensures_ret(attr: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let args = parse_macro_input!(attr as AttributeArgs);
+    let mut method = parse_macro_input!(item as ImplItemMethod);
+    if let Some(asyncness) = &method.sig.asyncness {
+        return Error::new(asyncness.span(), "returned values can't be checked for an async method").to_compile_error().into();
+    }
+    if returns_borrow_of_self(&method) {
+        return Error::new(method.sig.output.span(), "returned values can't be checked for a method that returns a borrow of self").to_compile_error().into();
+    }
+    let ty = match &method.sig.output {
+        ReturnType::Type(_, ty) => (**ty).clone(),
+        ReturnType::Default => return Error::new(method.sig.span(), "expected a method returning a value").to_compile_error().into(),
+    };
+
+    let mut rules = vec![];
+    let mut use_custom = false;
+    let mut errors = vec![];
+    for arg in &args {
+        parse_rule(arg, &mut rules, &mut use_custom, &mut errors);
+    }
+    check_rules(&rules, &mut errors);
+    if use_custom {
+        errors.push(Error::new(Span::call_site(), "use_custom is only supported on fields").to_compile_error());
+    }
+    if has_element_rules(&rules) {
+        errors.push(Error::new(Span::call_site(), "each, keys and values are only supported on fields").to_compile_error());
+    }
+    if !errors.is_empty() {
+        return quote! { #( #errors )* }.into();
+    }
+
+    let method_name = method.sig.ident.to_string();
+    let value = quote! { (__rep_ret) };
+    let checks = rules.iter().map(|rule| match rule_check(rule, &value, &ty, "ret") {
+        Some(RuleCheck { check, rule, expected, actual, message }) => quote! {
+            if !{ #check } {
+                rep::handle_violations(&rep::Violations::from(rep::Violation {
+                    type_name: String::from(std::any::type_name::<Self>()),
+                    variant: None,
+                    path: String::from("ret"),
+                    rule: String::from(#rule),
+                    expected: #expected,
+                    actual: #actual,
+                    message: format!("{} on exit from {}", #message, #method_name),
+                }));
+            }
+        },
+        None => quote! {
+            if let Err(mut violations) = __rep_ret.violations() {
+                violations.within("ret");
+                for violation in violations.iter_mut() {
+                    violation.message = format!("{} on exit from {}", violation.message, #method_name);
+                }
+                rep::handle_violations(&violations);
+            }
+        },
+    });
+    let enabled = checks_enabled(false);
+    let checks = quote! {
+        if #enabled {
+            #( #checks )*
+        }
+    };
+    check_on_exit(&mut method, quote! {}, checks);
+
+    method.to_token_stream().into()
+}
+
+/// Wraps the body of a method so that `exit` runs however the method returns, with the returned
+/// value bound to `__rep_ret`, after `entry` has run
+fn check_on_exit(method: &mut ImplItemMethod, entry: TokenStream, exit: TokenStream) {
+    let ret = closure_return(&method.sig.output);
+    let block = &method.block;
+    method.block = parse_quote! {
+        {
+            #entry
+            #[allow(clippy::redundant_closure_call)]
+            let __rep_ret = (|| #ret #block)();
+            #exit
+            __rep_ret
+        }
+    };
+}
+
+/// A marker for methods that `#[check_rep]` on an impl block should leave unchecked
+#[proc_macro_attribute]
+pub fn rep_skip(_attr: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    item
+}
@@ -1,6 +1,6 @@
+#![allow(dead_code)]
+
 use rep::*;
-#[macro_use]
-extern crate log;
 
 fn is_gt_zero(num: i32) -> bool {
     num > 0
@@ -42,7 +42,7 @@ impl CustomCheckRep for Line {
 			errors.push(String::from("self.x2 must equal self.y2"));
 		}
 
-		if errors.len() == 0 {
+		if errors.is_empty() {
 			Ok(())
 		} else {
 			Err(errors)
@@ -50,13 +50,28 @@ impl CustomCheckRep for Line {
 	}
 }
 
+#[derive(CheckRep)]
+struct Percent(#[rep(assert_le = 100)] u8);
+
+#[derive(CheckRep)]
+enum Conn {
+    Open {
+        #[rep(assert_ge = 0)]
+        fd: i32,
+        #[rep(assert_le = 4096)]
+        buf_len: usize,
+    },
+    Closed,
+}
+
 impl Line {
     #[ensure_rep]
     pub fn foo(&mut self) {}
 }
 
 fn main() {
-    // env_logger::init();
+    env_logger::init();
+    set_violation_handler(ViolationHandler::Log);
 
     let mut line = Line {
         start: Point { x: 50, y: 50 },
@@ -66,6 +81,11 @@ fn main() {
         y2: 10,
     };
 
+    let conn = Conn::Open { fd: 3, buf_len: 1024 };
+    conn.check_rep();
+    Conn::Closed.check_rep();
+    Percent(42).check_rep();
+
     line.foo();
     // Line::foo();
 }